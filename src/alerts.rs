@@ -0,0 +1,363 @@
+//! Alerts when access keys approach their data limit.
+//!
+//! A `UsageWatcher` compares the bytes transferred by each key with its data limit (or the
+//! server-wide limit) and reports a `UsageAlert` the first time a key crosses each threshold.
+//! Alerts are delivered through an `AlertSink`, such as `StdoutSink` or, with the `webhook`
+//! feature, `WebhookSink`.
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "blocking")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use outline_api::{StdoutSink, UsageWatcher};
+//! use std::time::Duration;
+//!
+//! let vpn = outline_api::OutlineVPN::builder()
+//!     .access_config(r#"{"apiUrl":"https://1.2.3.4:1234/secret","certSha256":"E2DE8E9A0A5BD9E8B3A8A5A9D5C8F8E2DE8E9A0A5BD9E8B3A8A5A9D5C8F82A75"}"#)?
+//!     .build()?;
+//!
+//! UsageWatcher::new()
+//!     .thresholds([80, 95, 100])
+//!     .run(&vpn, &StdoutSink::new(), Duration::from_secs(300));
+//! # }
+//! # #[cfg(not(feature = "blocking"))]
+//! # fn main() {}
+//! ```
+
+use crate::{AccessKey, AccessKeyId, ByteSize, Server, TransferMetrics};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+/// Error returned by an `AlertSink`.
+pub type SinkError = Box<dyn Error + Send + Sync>;
+
+/// A key whose usage crossed a threshold of its data limit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageAlert {
+    pub server_name: String,
+    pub key_id: AccessKeyId,
+    pub key_name: String,
+    pub bytes_transferred: ByteSize,
+    /// Limit of the key, or the server-wide limit if the key has none.
+    pub limit: ByteSize,
+    /// Percentage of the limit that was crossed, e.g. `80`.
+    pub threshold: u8,
+}
+
+impl UsageAlert {
+    /// Returns the fraction of the limit used, e.g. `0.85`.
+    pub fn utilization(&self) -> f64 {
+        if self.limit.as_u64() == 0 {
+            return f64::INFINITY;
+        }
+        self.bytes_transferred.as_u64() as f64 / self.limit.as_u64() as f64
+    }
+}
+
+impl fmt::Display for UsageAlert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Key {:?} ({}) on {} reached {}% of its data limit: {} of {}",
+            self.key_id.as_str(),
+            self.key_name,
+            self.server_name,
+            self.threshold,
+            self.bytes_transferred,
+            self.limit
+        )
+    }
+}
+
+/// Delivers usage alerts.
+///
+/// Implemented by `StdoutSink`, `WebhookSink` and closures taking a `&UsageAlert`.
+pub trait AlertSink {
+    /// Delivers one alert.
+    fn send(&self, alert: &UsageAlert) -> Result<(), SinkError>;
+}
+
+impl<F> AlertSink for F
+where
+    F: Fn(&UsageAlert) -> Result<(), SinkError>,
+{
+    fn send(&self, alert: &UsageAlert) -> Result<(), SinkError> {
+        self(alert)
+    }
+}
+
+/// Prints alerts to stdout, one per line, as text or as JSON.
+#[derive(Debug, Clone, Default)]
+pub struct StdoutSink {
+    json: bool,
+}
+
+impl StdoutSink {
+    /// Creates a sink printing alerts as text.
+    pub fn new() -> Self {
+        StdoutSink::default()
+    }
+
+    /// Creates a sink printing alerts as JSON, e.g. for log collectors.
+    pub fn json() -> Self {
+        StdoutSink { json: true }
+    }
+}
+
+impl AlertSink for StdoutSink {
+    fn send(&self, alert: &UsageAlert) -> Result<(), SinkError> {
+        let line = if self.json {
+            serde_json::to_string(alert)?
+        } else {
+            alert.to_string()
+        };
+        writeln!(io::stdout().lock(), "{}", line)?;
+        Ok(())
+    }
+}
+
+/// Posts each alert as JSON to a URL, e.g. a chat or incident webhook.
+///
+/// Available with the `webhook` feature. Public webhooks are verified against the Mozilla root
+/// certificates; pass a client to `with_client` to use other roots.
+#[cfg(feature = "webhook")]
+#[derive(Debug, Clone)]
+pub struct WebhookSink {
+    url: reqwest::Url,
+    client: reqwest::blocking::Client,
+}
+
+#[cfg(feature = "webhook")]
+impl WebhookSink {
+    /// Creates a sink posting to `url`, with a 10 second timeout.
+    ///
+    /// # Errors
+    ///
+    /// - `APIError::InvalidArgument`: If `url` is not a valid URL.
+    /// - `APIError::ClientBuild`: If the HTTP client can't be created.
+    pub fn new(url: &str) -> Result<Self, crate::APIError> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .map_err(crate::APIError::ClientBuild)?;
+        WebhookSink::with_client(url, client)
+    }
+
+    /// Creates a sink posting to `url` with a pre-built client.
+    ///
+    /// # Errors
+    ///
+    /// - `APIError::InvalidArgument`: If `url` is not a valid URL.
+    pub fn with_client(
+        url: &str,
+        client: reqwest::blocking::Client,
+    ) -> Result<Self, crate::APIError> {
+        let url = reqwest::Url::parse(url).map_err(|err| crate::APIError::InvalidArgument {
+            name: "url",
+            message: err.to_string(),
+        })?;
+        Ok(WebhookSink { url, client })
+    }
+}
+
+#[cfg(feature = "webhook")]
+impl AlertSink for WebhookSink {
+    fn send(&self, alert: &UsageAlert) -> Result<(), SinkError> {
+        self.client
+            .post(self.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(alert)?)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Watches the usage of access keys and reports thresholds as they are crossed.
+///
+/// Each threshold fires once per key. When usage falls back below a threshold, for example
+/// because the limit was raised or old traffic left the server's rolling window, the threshold
+/// can fire again. A key jumping past several thresholds at once only reports the highest one.
+///
+/// One watcher can check several servers: keys are told apart by the server name and their ID.
+#[derive(Debug, Clone)]
+pub struct UsageWatcher {
+    thresholds: Vec<u8>,
+    /// Highest threshold reported for each key, by server name and key ID.
+    reported: HashMap<(String, AccessKeyId), u8>,
+}
+
+impl Default for UsageWatcher {
+    fn default() -> Self {
+        UsageWatcher {
+            thresholds: vec![80, 100],
+            reported: HashMap::new(),
+        }
+    }
+}
+
+impl UsageWatcher {
+    /// Creates a watcher with thresholds at 80% and 100%.
+    pub fn new() -> Self {
+        UsageWatcher::default()
+    }
+
+    /// Sets the thresholds, as percentages of the limit.
+    pub fn thresholds(mut self, thresholds: impl IntoIterator<Item = u8>) -> Self {
+        self.thresholds = thresholds.into_iter().collect();
+        self.thresholds.sort_unstable();
+        self.thresholds.dedup();
+        self
+    }
+
+    /// Computes the usage of every key and returns the alerts for newly crossed thresholds.
+    ///
+    /// Keys without a limit of their own are measured against `server.access_key_data_limit`.
+    /// Keys with no limit at all never raise alerts.
+    pub fn check(
+        &mut self,
+        server: &Server,
+        keys: &[AccessKey],
+        metrics: &TransferMetrics,
+    ) -> Vec<UsageAlert> {
+        let (crossed, alerts) = self.evaluate(server, keys, metrics);
+        self.record(server, crossed);
+        alerts
+    }
+
+    /// Like `check`, but sends the alerts to `sink` and only records a threshold as reported once
+    /// its alert was delivered, so failed deliveries are retried by the next check.
+    ///
+    /// Returns the alerts that couldn't be delivered, with their errors.
+    pub fn check_and_send(
+        &mut self,
+        server: &Server,
+        keys: &[AccessKey],
+        metrics: &TransferMetrics,
+        sink: &dyn AlertSink,
+    ) -> Vec<(UsageAlert, SinkError)> {
+        let (mut crossed, alerts) = self.evaluate(server, keys, metrics);
+        let mut failures = Vec::new();
+        for alert in alerts {
+            if let Err(err) = sink.send(&alert) {
+                let key = (server.name.clone(), alert.key_id.clone());
+                match self.reported.get(&key) {
+                    Some(last) => crossed.insert(key, *last),
+                    None => crossed.remove(&key),
+                };
+                failures.push((alert, err));
+            }
+        }
+        self.record(server, crossed);
+        failures
+    }
+
+    /// Replaces the thresholds reported for the keys of `server` with the ones crossed now.
+    fn record(&mut self, server: &Server, crossed: HashMap<(String, AccessKeyId), u8>) {
+        // Keys that were deleted, or fell back below every threshold, start over
+        self.reported.retain(|(name, _), _| *name != server.name);
+        self.reported.extend(crossed);
+    }
+
+    /// Returns the highest threshold crossed by each key, and the alerts for the ones that
+    /// weren't reported yet.
+    fn evaluate(
+        &self,
+        server: &Server,
+        keys: &[AccessKey],
+        metrics: &TransferMetrics,
+    ) -> (HashMap<(String, AccessKeyId), u8>, Vec<UsageAlert>) {
+        let mut crossed_by_key = HashMap::new();
+        let mut alerts = Vec::new();
+
+        for key in keys {
+            let Some(limit) = key.data_limit.or(server.access_key_data_limit) else {
+                continue;
+            };
+            let bytes = metrics
+                .bytes_transferred_by_user_id
+                .get(&key.id)
+                .copied()
+                .unwrap_or(0);
+            let crossed = self
+                .thresholds
+                .iter()
+                .rev()
+                .find(|threshold| {
+                    u128::from(bytes) * 100
+                        >= u128::from(**threshold) * u128::from(limit.bytes.as_u64())
+                })
+                .copied();
+            let Some(crossed) = crossed else {
+                continue;
+            };
+
+            let reported_key = (server.name.clone(), key.id.clone());
+            if self
+                .reported
+                .get(&reported_key)
+                .map_or(true, |last| crossed > *last)
+            {
+                alerts.push(UsageAlert {
+                    server_name: server.name.clone(),
+                    key_id: key.id.clone(),
+                    key_name: key.name.clone(),
+                    bytes_transferred: ByteSize::new(bytes),
+                    limit: limit.bytes,
+                    threshold: crossed,
+                });
+            }
+            crossed_by_key.insert(reported_key, crossed);
+        }
+
+        (crossed_by_key, alerts)
+    }
+
+    /// Fetches the server info, keys and transfer metrics, then checks them.
+    #[cfg(feature = "blocking")]
+    pub fn poll(
+        &mut self,
+        vpn: &crate::blocking::OutlineVPN,
+    ) -> Result<Vec<UsageAlert>, crate::APIError> {
+        let (server, keys, metrics) = fetch(vpn)?;
+        Ok(self.check(&server, &keys, &metrics))
+    }
+
+    /// Polls the server every `interval` and sends the alerts to `sink`, forever.
+    ///
+    /// Failed requests and deliveries are logged and don't stop the watcher. Alerts that couldn't
+    /// be delivered are sent again at the next interval.
+    #[cfg(feature = "blocking")]
+    pub fn run(
+        &mut self,
+        vpn: &crate::blocking::OutlineVPN,
+        sink: &dyn AlertSink,
+        interval: std::time::Duration,
+    ) -> ! {
+        loop {
+            match fetch(vpn) {
+                Ok((server, keys, metrics)) => {
+                    for (alert, err) in self.check_and_send(&server, &keys, &metrics, sink) {
+                        log::warn!("Can't send the alert for key {:?}: {}", alert.key_id, err);
+                    }
+                }
+                Err(err) => log::warn!("Can't check the usage of access keys: {}", err),
+            }
+            std::thread::sleep(interval);
+        }
+    }
+}
+
+/// Fetches the server info, keys and transfer metrics checked by a `UsageWatcher`.
+#[cfg(feature = "blocking")]
+fn fetch(
+    vpn: &crate::blocking::OutlineVPN,
+) -> Result<(Server, Vec<AccessKey>, TransferMetrics), crate::APIError> {
+    let server = vpn.get_server_info()?;
+    let keys = vpn.list_access_keys()?.access_keys;
+    let metrics = vpn.get_each_access_key_data_transferred()?;
+    Ok((server, keys, metrics))
+}
@@ -0,0 +1,412 @@
+//! `outline-ctl`: manage an Outline VPN server from the command line.
+//!
+//! Credentials are read from `--api-url`/`--cert-sha256`, from the JSON printed by the Outline
+//! installer (`--access-config`, inline or as a file path), or from the matching `OUTLINE_*`
+//! environment variables. Results are printed as tables, or as JSON with `--output json`.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use outline_api::{APIError, AccessKey, ByteSize, CreateAccessKeyRequest, DataLimit, OutlineVPN};
+use serde::Serialize;
+use std::io::Write;
+use std::num::NonZeroU16;
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "outline-ctl", version, about = "Manage an Outline VPN server")]
+struct Cli {
+    #[command(flatten)]
+    connection: Connection,
+
+    /// Output format.
+    #[arg(long, short, value_enum, default_value_t = Output::Table, global = true)]
+    output: Output,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct Connection {
+    /// API URL of the server, including the secret.
+    #[arg(long, env = "OUTLINE_API_URL", global = true)]
+    api_url: Option<String>,
+
+    /// SHA-256 fingerprint of the server certificate.
+    #[arg(long, env = "OUTLINE_CERT_SHA256", global = true)]
+    cert_sha256: Option<String>,
+
+    /// Access config printed by the Outline installer, as JSON or as the path of a file holding it.
+    #[arg(long, env = "OUTLINE_ACCESS_CONFIG", global = true)]
+    access_config: Option<String>,
+
+    /// Timeout of each request, in seconds.
+    #[arg(long, env = "OUTLINE_TIMEOUT", default_value_t = 10, global = true)]
+    timeout: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Server settings.
+    #[command(subcommand)]
+    Server(ServerCommand),
+    /// Access keys.
+    #[command(subcommand)]
+    Keys(KeysCommand),
+    /// Data transfer metrics and metrics sharing.
+    #[command(subcommand)]
+    Metrics(MetricsCommand),
+}
+
+#[derive(Subcommand)]
+enum ServerCommand {
+    /// Show server information.
+    Info,
+    /// Rename the server.
+    Rename { name: String },
+    /// Change the hostname used in access keys.
+    Hostname { hostname: String },
+    /// Change the port of newly created access keys.
+    Port { port: NonZeroU16 },
+    /// Set a data limit for all access keys, e.g. `10GB`, `10GiB` or a number of bytes.
+    Limit { limit: ByteSize },
+    /// Remove the data limit of all access keys.
+    RemoveLimit,
+}
+
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// List access keys.
+    List,
+    /// Show an access key.
+    Get { id: String },
+    /// Create an access key.
+    Create(CreateArgs),
+    /// Delete an access key.
+    Delete { id: String },
+    /// Rename an access key.
+    Rename { id: String, name: String },
+    /// Set the data limit of an access key, e.g. `10GB`, `10GiB` or a number of bytes.
+    Limit { id: String, limit: ByteSize },
+    /// Remove the data limit of an access key.
+    RemoveLimit { id: String },
+    /// Print the access URL of a key as a QR code.
+    Qr {
+        id: String,
+        /// Image format. PNG is written as binary to stdout.
+        #[arg(long, value_enum, default_value_t = QrFormat::Unicode)]
+        format: QrFormat,
+        /// Size of each module in pixels, for SVG and PNG.
+        #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+        module_size: u32,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum QrFormat {
+    Unicode,
+    Svg,
+    Png,
+}
+
+#[derive(Args)]
+struct CreateArgs {
+    /// Custom ID of the key. Requires a recent server version.
+    #[arg(long)]
+    id: Option<String>,
+    #[arg(long)]
+    name: Option<String>,
+    /// Encryption method, e.g. `chacha20-ietf-poly1305`.
+    #[arg(long)]
+    method: Option<String>,
+    #[arg(long)]
+    port: Option<NonZeroU16>,
+    #[arg(long)]
+    password: Option<String>,
+    /// Data limit, e.g. `10GB`, `10GiB` or a number of bytes.
+    #[arg(long)]
+    limit: Option<ByteSize>,
+}
+
+#[derive(Subcommand)]
+enum MetricsCommand {
+    /// Show the bytes transferred by each access key.
+    Transfer,
+    /// Show whether anonymous metrics are shared.
+    Status,
+    /// Share anonymous metrics.
+    Enable,
+    /// Stop sharing anonymous metrics.
+    Disable,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), APIError> {
+    let vpn = connect(&cli.connection)?;
+    let output = cli.output;
+
+    match cli.command {
+        Command::Server(command) => match command {
+            ServerCommand::Info => {
+                let server = vpn.get_server_info()?;
+                print(output, &server, || {
+                    let limit = format_limit(server.access_key_data_limit);
+                    print_table(
+                        &["FIELD", "VALUE"],
+                        vec![
+                            vec!["Name".to_string(), server.name.clone()],
+                            vec!["Server ID".to_string(), server.server_id.clone()],
+                            vec!["Version".to_string(), server.version.clone()],
+                            vec![
+                                "Hostname".to_string(),
+                                server.hostname_for_access_keys.clone(),
+                            ],
+                            vec![
+                                "Port for new keys".to_string(),
+                                server.port_for_new_access_keys.to_string(),
+                            ],
+                            vec!["Data limit".to_string(), limit],
+                            vec![
+                                "Metrics enabled".to_string(),
+                                server.metrics_enabled.to_string(),
+                            ],
+                        ],
+                    )
+                });
+            }
+            ServerCommand::Rename { name } => {
+                vpn.rename_server(&name)?;
+                done(output, "Server renamed");
+            }
+            ServerCommand::Hostname { hostname } => {
+                vpn.change_hostname_for_access_keys(&hostname)?;
+                done(output, "Hostname changed");
+            }
+            ServerCommand::Port { port } => {
+                vpn.change_default_port_for_newly_created_access(port)?;
+                done(output, "Port for new access keys changed");
+            }
+            ServerCommand::Limit { limit } => {
+                vpn.set_data_transfer_limit_for_all_access_keys(limit)?;
+                done(output, "Data limit set for all access keys");
+            }
+            ServerCommand::RemoveLimit => {
+                vpn.remove_data_limit_for_all_access_keys()?;
+                done(output, "Data limit removed for all access keys");
+            }
+        },
+        Command::Keys(command) => match command {
+            KeysCommand::List => {
+                let keys = vpn.list_access_keys()?.access_keys;
+                print(output, &keys, || print_keys(&keys));
+            }
+            KeysCommand::Get { id } => {
+                let key = vpn.get_access_key_by_id(id)?;
+                print(output, &key, || print_keys(std::slice::from_ref(&key)));
+            }
+            KeysCommand::Create(args) => {
+                let request = CreateAccessKeyRequest {
+                    name: args.name,
+                    method: args.method,
+                    port: args.port,
+                    password: args.password,
+                    limit: args.limit.map(DataLimit::new),
+                };
+                let key = match args.id {
+                    Some(id) => vpn.create_access_key_with_id(id, &request)?,
+                    None => vpn.create_access_key_with_options(&request)?,
+                };
+                print(output, &key, || print_keys(std::slice::from_ref(&key)));
+            }
+            KeysCommand::Delete { id } => {
+                vpn.delete_access_key_by_id(id)?;
+                done(output, "Access key deleted");
+            }
+            KeysCommand::Rename { id, name } => {
+                vpn.change_name_for_access_key(id, &name)?;
+                done(output, "Access key renamed");
+            }
+            KeysCommand::Limit { id, limit } => {
+                vpn.set_data_transfer_limit_by_id(id, limit)?;
+                done(output, "Data limit set");
+            }
+            KeysCommand::RemoveLimit { id } => {
+                vpn.del_data_transfer_limit_by_id(id)?;
+                done(output, "Data limit removed");
+            }
+            KeysCommand::Qr {
+                id,
+                format,
+                module_size,
+            } => {
+                let key = vpn.get_access_key_by_id(id)?;
+                let code = key.qr_code().map_err(|err| APIError::InvalidArgument {
+                    name: "id",
+                    message: err.to_string(),
+                })?;
+                let too_large = |err: outline_api::QrError| APIError::InvalidArgument {
+                    name: "module_size",
+                    message: err.to_string(),
+                };
+                match format {
+                    QrFormat::Unicode => print!("{}", code.to_unicode()),
+                    QrFormat::Svg => print!("{}", code.to_svg(module_size).map_err(too_large)?),
+                    QrFormat::Png => {
+                        let png = code.to_png(module_size).map_err(too_large)?;
+                        let mut stdout = std::io::stdout().lock();
+                        // A closed pipe only means nobody reads the image anymore
+                        let _ = stdout.write_all(&png);
+                    }
+                }
+            }
+        },
+        Command::Metrics(command) => match command {
+            MetricsCommand::Transfer => {
+                let metrics = vpn.get_each_access_key_data_transferred()?;
+                print(output, &metrics, || {
+                    let mut rows: Vec<_> = metrics.bytes_transferred_by_user_id.iter().collect();
+                    rows.sort();
+                    print_table(
+                        &["ID", "BYTES"],
+                        rows.into_iter()
+                            .map(|(id, bytes)| vec![id.to_string(), bytes.to_string()])
+                            .collect(),
+                    )
+                });
+            }
+            MetricsCommand::Status => {
+                let metrics = vpn.get_whether_metrics_is_being_shared()?;
+                print(output, &metrics, || {
+                    println!(
+                        "Metrics sharing is {}",
+                        if metrics.metrics_enabled {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        }
+                    )
+                });
+            }
+            MetricsCommand::Enable => {
+                vpn.enable_or_disable_sharing_metrics(true)?;
+                done(output, "Metrics sharing enabled");
+            }
+            MetricsCommand::Disable => {
+                vpn.enable_or_disable_sharing_metrics(false)?;
+                done(output, "Metrics sharing disabled");
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Builds the client from the access config, overridden by the individual flags.
+fn connect(connection: &Connection) -> Result<OutlineVPN, APIError> {
+    let mut builder = OutlineVPN::builder();
+    if let Some(access_config) = &connection.access_config {
+        let json = if access_config.trim_start().starts_with('{') {
+            access_config.clone()
+        } else {
+            std::fs::read_to_string(access_config).map_err(|err| APIError::InvalidConfig {
+                field: "access_config",
+                message: format!("can't read {}: {}", access_config, err),
+            })?
+        };
+        builder = builder.access_config(&json)?;
+    }
+    if let Some(api_url) = &connection.api_url {
+        builder = builder.api_url(api_url);
+    }
+    if let Some(cert_sha256) = &connection.cert_sha256 {
+        builder = builder.cert_sha256(cert_sha256);
+    }
+
+    builder
+        .timeout(Duration::from_secs(connection.timeout))
+        .user_agent(concat!("outline-ctl/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
+/// Prints `value` as JSON, or calls `table` to print it for humans.
+fn print<T: Serialize>(output: Output, value: &T, table: impl FnOnce()) {
+    match output {
+        Output::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(value).expect("API models should serialize to JSON")
+        ),
+        Output::Table => table(),
+    }
+}
+
+/// Confirms an operation without a result. JSON output stays empty so it can be piped.
+fn done(output: Output, message: &str) {
+    if output == Output::Table {
+        println!("{}", message);
+    }
+}
+
+fn print_keys(keys: &[AccessKey]) {
+    print_table(
+        &["ID", "NAME", "PORT", "METHOD", "DATA LIMIT", "ACCESS URL"],
+        keys.iter()
+            .map(|key| {
+                vec![
+                    key.id.to_string(),
+                    key.name.clone(),
+                    key.port.to_string(),
+                    key.method.clone(),
+                    format_limit(key.data_limit),
+                    key.access_url.clone(),
+                ]
+            })
+            .collect(),
+    )
+}
+
+fn format_limit(limit: Option<DataLimit>) -> String {
+    match limit {
+        Some(limit) => limit.bytes.to_string(),
+        None => "-".to_string(),
+    }
+}
+
+/// Prints rows with each column padded to its widest cell.
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let headers = headers.iter().map(|header| header.to_string()).collect();
+    for row in std::iter::once(headers).chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
@@ -0,0 +1,92 @@
+//! `outline-exporter`: serve the metrics of Outline VPN servers to Prometheus.
+//!
+//! Each `--server NAME=ACCESS_CONFIG` adds a server, where `ACCESS_CONFIG` is the JSON printed by
+//! the Outline installer, inline or as a file path. The metrics are served at `/metrics`.
+
+use clap::Parser;
+use outline_api::{APIError, Exporter, Fleet, OutlineVPN};
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(
+    name = "outline-exporter",
+    version,
+    about = "Export Outline VPN metrics to Prometheus"
+)]
+struct Cli {
+    /// Server to poll, as `NAME=ACCESS_CONFIG`. Can be repeated.
+    #[arg(long = "server", value_name = "NAME=ACCESS_CONFIG", required = true)]
+    servers: Vec<String>,
+
+    /// Address to serve the metrics on.
+    #[arg(
+        long,
+        env = "OUTLINE_EXPORTER_LISTEN",
+        default_value = "127.0.0.1:9800"
+    )]
+    listen: String,
+
+    /// Seconds between polls.
+    #[arg(
+        long,
+        env = "OUTLINE_EXPORTER_INTERVAL",
+        default_value_t = 60,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    interval: u64,
+
+    /// Request timeout in seconds.
+    #[arg(long, env = "OUTLINE_TIMEOUT", default_value_t = 10)]
+    timeout: u64,
+}
+
+fn main() -> ExitCode {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Warn)
+        .parse_default_env()
+        .init();
+
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let mut fleet = Fleet::new();
+    for server in &cli.servers {
+        let (name, access_config) = server
+            .split_once('=')
+            .ok_or_else(|| format!("invalid --server {:?}: expected NAME=ACCESS_CONFIG", server))?;
+        fleet.insert(name, connect(access_config, cli.timeout)?);
+    }
+
+    Exporter::new(fleet)
+        .interval(Duration::from_secs(cli.interval))
+        .serve(&cli.listen)
+        .map_err(|err| format!("can't serve metrics on {}: {}", cli.listen, err))?;
+    Ok(())
+}
+
+/// Builds the client from inline access config JSON, or from the file it points to.
+fn connect(access_config: &str, timeout: u64) -> Result<OutlineVPN, APIError> {
+    let json = if access_config.trim_start().starts_with('{') {
+        access_config.to_string()
+    } else {
+        std::fs::read_to_string(access_config).map_err(|err| APIError::InvalidConfig {
+            field: "access_config",
+            message: format!("can't read {}: {}", access_config, err),
+        })?
+    };
+
+    OutlineVPN::builder()
+        .access_config(&json)?
+        .timeout(Duration::from_secs(timeout))
+        .user_agent(concat!("outline-exporter/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
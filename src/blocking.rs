@@ -0,0 +1,517 @@
+//! Blocking client for the Outline VPN Server management API.
+//!
+//! Available with the `blocking` feature (enabled by default). The types of this module are also
+//! re-exported at the crate root.
+
+use crate::builder::OutlineVPNBuilder;
+use crate::tls;
+use crate::{
+    access_key_path, handle_json_api_result, handle_response_status, is_unsupported_route,
+    json_body, APIError, AccessKey, AccessKeyId, AccessKeyList, CreateAccessKeyRequest,
+    ErrorResponse, MetricsEnabled, RawResponse, Server, TransferMetrics, ACCESS_KEYS_ENDPOINT,
+    CHANGE_PORT_ENDPOINT, HOSTNAME_ENDPOINT, KEY_DATA_LIMIT_ENDPOINT, METRICS_ENDPOINT,
+    NAME_ENDPOINT, SERVER_ENDPOINT,
+};
+use crate::{join_api_url, redact_api_url, validate_port, ByteSize, RetryPolicy};
+use crate::{
+    DataLimit, DataLimitRequest, HostnameRequest, MetricsEnabledRequest, NameRequest, PortRequest,
+};
+use log::debug;
+use reqwest::blocking::Client;
+use reqwest::Url;
+use std::fmt;
+use std::num::NonZeroU16;
+use std::thread;
+use std::time::Duration;
+
+/// Represents a client for interacting with the Outline VPN Server API.
+///
+/// The `OutlineVPN` struct provides methods to perform various operations on the Outline VPN server
+/// such as retrieving server information, changing settings, creating access keys, and more.
+///
+/// # Fields
+///
+/// - `api_url`: The URL (including `secret`) of the Outline VPN server API.
+/// - `session`: A reqwest HTTP client used to make API requests.
+/// - `request_timeout_in_sec`: The time to set the timeout for API requests, if any.
+/// - `retry_policy`: The policy for retrying idempotent requests, if any.
+///
+/// The client owns its configuration and is `Clone`, `Send` and `Sync`, so it can be stored in
+/// long-lived structs or shared across threads. Clones share the same connection pool. The `Debug`
+/// output doesn't include the `secret` part of the API URL.
+#[derive(Clone)]
+pub struct OutlineVPN {
+    api_url: Url,
+    session: Client,
+    request_timeout_in_sec: Option<Duration>,
+    retry_policy: Option<RetryPolicy>,
+}
+
+impl fmt::Debug for OutlineVPN {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OutlineVPN")
+            .field("api_url", &redact_api_url(&self.api_url))
+            .field("request_timeout_in_sec", &self.request_timeout_in_sec)
+            .field("retry_policy", &self.retry_policy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OutlineVPN {
+    /// Returns a builder to configure and validate a new `OutlineVPN` client.
+    pub fn builder() -> OutlineVPNBuilder<Client> {
+        OutlineVPNBuilder::new()
+    }
+}
+
+impl OutlineVPNBuilder<Client> {
+    /// Validates the settings and builds the `OutlineVPN` client.
+    ///
+    /// # Errors
+    ///
+    /// - `APIError::InvalidConfig`: If the API URL or the certificate fingerprint is missing or
+    ///   malformed, or if options were set that can't be applied to a pre-built client.
+    /// - `APIError::ClientBuild`: If the HTTP client could not be built.
+    pub fn build(self) -> Result<OutlineVPN, APIError> {
+        let parts = self.into_parts()?;
+        let session = match parts.client {
+            Some(client) => client,
+            None => {
+                let cert_sha256 = parts.cert_sha256.as_deref().unwrap_or_default();
+                let mut builder =
+                    Client::builder().use_preconfigured_tls(tls::pinned_client_config(cert_sha256));
+                if let Some(connect_timeout) = parts.connect_timeout {
+                    builder = builder.connect_timeout(connect_timeout);
+                }
+                if let Some(user_agent) = parts.user_agent {
+                    builder = builder.user_agent(user_agent);
+                }
+                if let Some(proxy) = parts.proxy {
+                    builder = builder.proxy(proxy);
+                }
+                builder.build().map_err(APIError::ClientBuild)?
+            }
+        };
+
+        Ok(OutlineVPN {
+            api_url: parts.api_url,
+            session,
+            request_timeout_in_sec: parts.timeout,
+            retry_policy: parts.retry_policy,
+        })
+    }
+}
+
+impl OutlineVPN {
+    /// Sends a request, retrying it according to the retry policy.
+    fn call_api(
+        &self,
+        api_path: &str,
+        request_method: reqwest::Method,
+        request_body: String,
+    ) -> Result<RawResponse, APIError> {
+        let mut attempt = 1;
+        loop {
+            let result = self.send_request(api_path, request_method.clone(), request_body.clone());
+            let delay = self
+                .retry_policy
+                .as_ref()
+                .and_then(|policy| policy.retry_delay(&request_method, api_path, attempt, &result));
+            match delay {
+                Some(delay) => {
+                    debug!(
+                        "Retrying {} {} in {:?} after attempt {}",
+                        request_method, api_path, delay, attempt
+                    );
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                None => return result,
+            }
+        }
+    }
+
+    fn send_request(
+        &self,
+        api_path: &str,
+        request_method: reqwest::Method,
+        request_body: String,
+    ) -> Result<RawResponse, APIError> {
+        let url = join_api_url(&self.api_url, api_path);
+        debug!("URL: {}", url);
+        debug!("Method: {:?}", request_method);
+        debug!("Request Body: {}", request_body);
+        let mut request = self.session.request(request_method, &url);
+        if let Some(timeout) = self.request_timeout_in_sec {
+            request = request.timeout(timeout);
+        }
+        let response = request
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(request_body)
+            .send()
+            .map_err(|source| APIError::from_transport(api_path, source))?;
+        let status = response.status();
+        let body = response
+            .text()
+            .map_err(|source| APIError::from_transport(api_path, source))?;
+
+        Ok(RawResponse { status, body })
+    }
+
+    /// Get server information.
+    ///
+    /// Responses:
+    ///
+    /// - `200` – Server information.
+    pub fn get_server_info(&self) -> Result<Server, APIError> {
+        let response = self.call_api(SERVER_ENDPOINT, reqwest::Method::GET, String::new())?;
+
+        handle_json_api_result(response, SERVER_ENDPOINT)
+    }
+
+    /// Change hostname for access keys.
+    ///
+    /// Responses:
+    ///
+    ///  - `204` – The hostname was successfully changed.
+    ///  - `400` – An invalid hostname or IP address was provided.
+    ///  - `500` – An internal error occurred.  This could be thrown if there were network errors while validating the hostname.
+    pub fn change_hostname_for_access_keys(&self, hostname: &str) -> Result<(), APIError> {
+        let body = json_body(&HostnameRequest { hostname });
+        let response = self.call_api(HOSTNAME_ENDPOINT, reqwest::Method::PUT, body)?;
+
+        handle_response_status(response, HOSTNAME_ENDPOINT)
+    }
+
+    /// Change default port for newly created access keys.
+    ///
+    /// Responses:
+    ///
+    /// - `204` – The default port was successfully changed.
+    /// - `400` – The requested port wasn't an integer from 1 through 65535, or the request had no port parameter.
+    /// - `409` – The requested port was already in use by another service.
+    ///
+    /// Accepts a `NonZeroU16` or any integer convertible into one, such as `u16`. Port `0` is
+    /// rejected with `APIError::InvalidArgument` without contacting the server.
+    pub fn change_default_port_for_newly_created_access(
+        &self,
+        port: impl TryInto<NonZeroU16>,
+    ) -> Result<(), APIError> {
+        let port = validate_port(port)?;
+        let body = json_body(&PortRequest { port });
+        let response = self.call_api(CHANGE_PORT_ENDPOINT, reqwest::Method::PUT, body)?;
+
+        handle_response_status(response, CHANGE_PORT_ENDPOINT)
+    }
+
+    /// Set data transfer limit for all access keys, as a `ByteSize` or a number of bytes.
+    ///
+    /// Responses:
+    ///
+    /// - `204` – Access key data limit set successfully.
+    /// - `400` – Invalid data limit.
+    pub fn set_data_transfer_limit_for_all_access_keys(
+        &self,
+        limit: impl Into<ByteSize>,
+    ) -> Result<(), APIError> {
+        let body = json_body(&DataLimitRequest {
+            limit: DataLimit::new(limit),
+        });
+        let response = self.call_api(KEY_DATA_LIMIT_ENDPOINT, reqwest::Method::PUT, body)?;
+
+        handle_response_status(response, KEY_DATA_LIMIT_ENDPOINT)
+    }
+
+    /// Remove data transfer limit for all access keys.
+    ///
+    /// Responses:
+    ///
+    /// - `204` – Access key limit deleted successfully.
+    pub fn remove_data_limit_for_all_access_keys(&self) -> Result<(), APIError> {
+        let response = self.call_api(
+            KEY_DATA_LIMIT_ENDPOINT,
+            reqwest::Method::DELETE,
+            String::new(),
+        )?;
+
+        handle_response_status(response, KEY_DATA_LIMIT_ENDPOINT)
+    }
+
+    /// Rename server.
+    ///
+    /// Responses:
+    ///
+    /// - `204` – Server renamed successfully.
+    /// - `400` – Invalid name.
+    pub fn rename_server(&self, name: &str) -> Result<(), APIError> {
+        let body = json_body(&NameRequest { name });
+        let response = self.call_api(NAME_ENDPOINT, reqwest::Method::PUT, body)?;
+
+        handle_response_status(response, NAME_ENDPOINT)
+    }
+
+    /// Create new access key.
+    ///
+    /// Responses:
+    ///
+    /// - `201` – The newly created access key.
+    pub fn create_access_key(&self) -> Result<AccessKey, APIError> {
+        self.create_access_key_with_options(&CreateAccessKeyRequest::default())
+    }
+
+    /// Create new access key with the given name, encryption method, port, password and data limit.
+    ///
+    /// The key is provisioned in a single request, so a failure never leaves a half-configured key
+    /// behind.
+    ///
+    /// Responses:
+    ///
+    /// - `201` – The newly created access key.
+    /// - `400` – Invalid request (e.g. unsupported method or invalid data limit).
+    /// - `409` – The requested port was already in use by another service.
+    pub fn create_access_key_with_options(
+        &self,
+        request: &CreateAccessKeyRequest,
+    ) -> Result<AccessKey, APIError> {
+        let body = json_body(request);
+        let response = self.call_api(ACCESS_KEYS_ENDPOINT, reqwest::Method::POST, body)?;
+
+        handle_json_api_result(response, ACCESS_KEYS_ENDPOINT)
+    }
+
+    /// Create new access key with the given ID.
+    ///
+    /// Unlike `create_access_key_with_options`, the ID is chosen by the caller, which makes
+    /// provisioning idempotent: creating the same key twice fails with
+    /// `APIError::AccessKeyAlreadyExists` instead of creating a duplicate. Requires a server that
+    /// supports `PUT /access-keys/{id}`.
+    ///
+    /// Responses:
+    ///
+    /// - `201` – The newly created access key.
+    /// - `400` – Invalid request (e.g. unsupported method or invalid data limit).
+    /// - `409` – An access key with the requested ID already exists.
+    pub fn create_access_key_with_id(
+        &self,
+        id: impl Into<AccessKeyId>,
+        request: &CreateAccessKeyRequest,
+    ) -> Result<AccessKey, APIError> {
+        let body = json_body(request);
+        let api_path = access_key_path(&id.into(), "");
+        let response = self.call_api(&api_path, reqwest::Method::PUT, body)?;
+
+        handle_json_api_result(response, &api_path)
+    }
+
+    /// Display complete list of the access keys.
+    ///
+    /// Responses:
+    ///
+    /// - `200` – List of access keys.
+    pub fn list_access_keys(&self) -> Result<AccessKeyList, APIError> {
+        let response = self.call_api(ACCESS_KEYS_ENDPOINT, reqwest::Method::GET, String::new())?;
+
+        handle_json_api_result(response, ACCESS_KEYS_ENDPOINT)
+    }
+
+    /// Get access key by ID.
+    ///
+    /// Some server versions don't implement `GET /access-keys/{id}`. For them, the key is looked
+    /// up in `list_access_keys` instead, so `APIError::AccessKeyInexistent` always means that the
+    /// key doesn't exist.
+    ///
+    /// Responses:
+    ///
+    /// - `200` – The access key.
+    /// - `404` – Access key inexistent.
+    pub fn get_access_key_by_id(&self, id: impl Into<AccessKeyId>) -> Result<AccessKey, APIError> {
+        let id = id.into();
+        let api_path = access_key_path(&id, "");
+        let response = self.call_api(&api_path, reqwest::Method::GET, String::new())?;
+        if !is_unsupported_route(&response) {
+            return handle_json_api_result(response, &api_path);
+        }
+
+        debug!(
+            "{} is not supported by the server, searching the key list",
+            api_path
+        );
+        self.list_access_keys()?
+            .access_keys
+            .into_iter()
+            .find(|key| key.id == id)
+            .ok_or_else(|| {
+                APIError::AccessKeyInexistent(ErrorResponse {
+                    status: reqwest::StatusCode::NOT_FOUND,
+                    endpoint: api_path,
+                    body: String::new(),
+                })
+            })
+    }
+
+    /// Delete access key by ID.
+    ///
+    /// Responses:
+    ///
+    /// - `204` – Access key deleted successfully.
+    /// - `404` – Access key inexistent.
+    pub fn delete_access_key_by_id(&self, id: impl Into<AccessKeyId>) -> Result<(), APIError> {
+        let api_path = access_key_path(&id.into(), "");
+        let response = self.call_api(&api_path, reqwest::Method::DELETE, String::new())?;
+
+        handle_response_status(response, &api_path)
+    }
+
+    /// Change name for access key (by ID).
+    ///
+    /// Responses:
+    ///
+    /// - `204` – Access key renamed successfully.
+    /// - `404` – Access key inexistent.
+    pub fn change_name_for_access_key(
+        &self,
+        id: impl Into<AccessKeyId>,
+        username: &str,
+    ) -> Result<(), APIError> {
+        let body = json_body(&NameRequest { name: username });
+        let api_path = access_key_path(&id.into(), "/name");
+        let response = self.call_api(&api_path, reqwest::Method::PUT, body)?;
+
+        handle_response_status(response, &api_path)
+    }
+
+    /// Set data transfer limit by ID, as a `ByteSize` or a number of bytes.
+    ///
+    /// Responses:
+    ///
+    /// - `204` – Access key limit set successfully.
+    /// - `400` – Invalid data limit.
+    /// - `404` –  Access key inexistent.
+    pub fn set_data_transfer_limit_by_id(
+        &self,
+        id: impl Into<AccessKeyId>,
+        limit: impl Into<ByteSize>,
+    ) -> Result<(), APIError> {
+        let body = json_body(&DataLimitRequest {
+            limit: DataLimit::new(limit),
+        });
+        let api_path = access_key_path(&id.into(), "/data-limit");
+        let response = self.call_api(&api_path, reqwest::Method::PUT, body)?;
+
+        handle_response_status(response, &api_path)
+    }
+
+    /// Remove data transfer limit by ID.
+    ///
+    /// Responses:
+    ///
+    /// - `204` – Access key limit deleted successfully.
+    /// - `404` – Access key inexistent.
+    pub fn del_data_transfer_limit_by_id(
+        &self,
+        id: impl Into<AccessKeyId>,
+    ) -> Result<(), APIError> {
+        let api_path = access_key_path(&id.into(), "/data-limit");
+        let response = self.call_api(&api_path, reqwest::Method::DELETE, String::new())?;
+
+        handle_response_status(response, &api_path)
+    }
+
+    /// Get data transfer stats for each access key in bytes.
+    ///
+    /// Responses:
+    ///
+    /// - `200` – The data transferred by each access key.
+    pub fn get_each_access_key_data_transferred(&self) -> Result<TransferMetrics, APIError> {
+        let api_path = format!("{}/transfer", METRICS_ENDPOINT);
+        let response = self.call_api(&api_path, reqwest::Method::GET, String::new())?;
+
+        handle_json_api_result(response, &api_path)
+    }
+
+    /// Get 'Share anonymous metrics' status.
+    ///
+    /// Responses:
+    ///
+    /// - `200` – The metrics enabled setting.
+    pub fn get_whether_metrics_is_being_shared(&self) -> Result<MetricsEnabled, APIError> {
+        let api_path = format!("{}/enabled", METRICS_ENDPOINT);
+        let response = self.call_api(&api_path, reqwest::Method::GET, String::new())?;
+
+        handle_json_api_result(response, &api_path)
+    }
+
+    /// Enable or disable 'Share anonymous metrics' setting.
+    ///
+    /// Responses:
+    ///
+    /// - `204` – Setting successful.
+    /// - `400` – Invalid request.
+    pub fn enable_or_disable_sharing_metrics(&self, metrics_enabled: bool) -> Result<(), APIError> {
+        let body = json_body(&MetricsEnabledRequest { metrics_enabled });
+        let api_path = format!("{}/enabled", METRICS_ENDPOINT);
+        let response = self.call_api(&api_path, reqwest::Method::PUT, body)?;
+
+        handle_response_status(response, &api_path)
+    }
+}
+
+/// Creates a new `OutlineVPN` client to interact with the Outline VPN Server management API.
+///
+/// This function initializes and configures an `OutlineVPN` client with the provided parameters.
+/// It panics if `api_url` isn't a valid URL or if the HTTP client can't be built; prefer
+/// `OutlineVPN::builder()`, which reports these problems as errors.
+///
+/// # Arguments
+///
+/// - `cert_sha256`: A reference to a string representing the SHA-256 hash of the server's certificate.
+///   Connections to a server presenting any other certificate fail with `APIError::CertificatePinMismatch`.
+/// - `api_url`: A reference to a string representing the URL of the Outline VPN server API.
+/// - `request_timeout_in_sec`: `Duration` specifying the timeout for API requests.
+///
+/// # Returns
+///
+/// Returns an `OutlineVPN` client configured with the specified parameters.
+///
+/// # Examples:
+///
+/// ## Creating an OutlineVPM API client:
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// // Reading from the `config.rs` is preferred way, see README.md at github repo
+/// // https://github.com/sigseg5/outline-api/blob/master/README.md
+/// let api_url = "https://example.com/secret";
+/// let cert_sha256 = "cert_sha256_hash";
+/// let request_timeout = Duration::from_secs(10);
+///
+/// let outline_vpn = outline_api::new(cert_sha256, api_url, request_timeout);
+///
+/// // Performing operations using the Client:
+///
+/// match outline_vpn.get_server_info() {
+///     Ok(server_info) => {
+///         println!("Server name: {}", server_info.name);
+///     },
+///     Err(err) => {
+///         eprintln!("Error: {}", err);
+///     }
+/// }
+/// ```
+#[deprecated(note = "use `OutlineVPN::builder()`, which validates its input instead of panicking")]
+pub fn new(cert_sha256: &str, api_url: &str, request_timeout: Duration) -> OutlineVPN {
+    // The server uses a self-issued certificate, so instead of validating the chain the handshake
+    // checks that the leaf certificate matches `cert_sha256`
+    let session = Client::builder()
+        .use_preconfigured_tls(tls::pinned_client_config(cert_sha256))
+        .build()
+        .unwrap();
+
+    OutlineVPN {
+        api_url: Url::parse(api_url).unwrap(),
+        session,
+        request_timeout_in_sec: Some(request_timeout),
+        retry_policy: None,
+    }
+}
@@ -0,0 +1,263 @@
+//! Fallible construction of `OutlineVPN` clients.
+
+use crate::{redact_api_url, APIError, RetryPolicy};
+use reqwest::Url;
+use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+
+/// Builder for `OutlineVPN` clients, created with `OutlineVPN::builder()`.
+///
+/// Unlike `new()`, the builder validates its input and reports problems as
+/// `APIError::InvalidConfig` instead of panicking. `C` is the `reqwest` client type used by the
+/// blocking (`reqwest::blocking::Client`) or async (`reqwest::Client`) `OutlineVPN`.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "blocking")]
+/// # fn main() -> Result<(), outline_api::APIError> {
+/// use outline_api::OutlineVPN;
+/// use std::time::Duration;
+///
+/// // The JSON printed by the Outline installer
+/// let access_config = r#"{"apiUrl":"https://127.0.0.1:1234/secret","certSha256":"E2DE8E9A0A5BD9E8B3A8A5A9D5C8F8E2DE8E9A0A5BD9E8B3A8A5A9D5C8F82A75"}"#;
+///
+/// let outline_vpn = OutlineVPN::builder()
+///     .access_config(access_config)?
+///     .timeout(Duration::from_secs(10))
+///     .user_agent("my-app/1.0")
+///     .build()?;
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "blocking"))]
+/// # fn main() {}
+/// ```
+pub struct OutlineVPNBuilder<C> {
+    api_url: Option<String>,
+    cert_sha256: Option<String>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    user_agent: Option<String>,
+    proxy: Option<reqwest::Proxy>,
+    retry_policy: Option<RetryPolicy>,
+    client: Option<C>,
+}
+
+/// Access config printed by the Outline installer.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccessConfig {
+    api_url: String,
+    cert_sha256: String,
+}
+
+/// Validated builder settings, consumed by the client-specific `build()`.
+pub(crate) struct BuilderParts<C> {
+    pub(crate) api_url: Url,
+    pub(crate) cert_sha256: Option<String>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) user_agent: Option<String>,
+    pub(crate) proxy: Option<reqwest::Proxy>,
+    pub(crate) retry_policy: Option<RetryPolicy>,
+    pub(crate) client: Option<C>,
+}
+
+impl<C> fmt::Debug for OutlineVPNBuilder<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let api_url = self
+            .api_url
+            .as_deref()
+            .map(|api_url| match Url::parse(api_url) {
+                Ok(url) => redact_api_url(&url),
+                Err(_) => "<invalid>".to_string(),
+            });
+        f.debug_struct("OutlineVPNBuilder")
+            .field("api_url", &api_url)
+            .field("cert_sha256", &self.cert_sha256)
+            .field("timeout", &self.timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("user_agent", &self.user_agent)
+            .field("proxy", &self.proxy)
+            .field("retry_policy", &self.retry_policy)
+            .field("client", &self.client.is_some())
+            .finish()
+    }
+}
+
+impl<C> OutlineVPNBuilder<C> {
+    pub(crate) fn new() -> Self {
+        OutlineVPNBuilder {
+            api_url: None,
+            cert_sha256: None,
+            timeout: None,
+            connect_timeout: None,
+            user_agent: None,
+            proxy: None,
+            retry_policy: None,
+            client: None,
+        }
+    }
+
+    /// Sets the URL (including `secret`) of the Outline VPN server API, which must use `https`.
+    pub fn api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.api_url = Some(api_url.into());
+        self
+    }
+
+    /// Sets the SHA-256 fingerprint of the server certificate, as 64 hex characters with or
+    /// without colons between bytes.
+    pub fn cert_sha256(mut self, cert_sha256: impl Into<String>) -> Self {
+        self.cert_sha256 = Some(cert_sha256.into());
+        self
+    }
+
+    /// Sets both the API URL and the certificate fingerprint from the JSON printed by the
+    /// Outline installer, e.g. `{"apiUrl":"https://1.2.3.4:1234/secret","certSha256":"E2DE8..."}`.
+    pub fn access_config(mut self, json: &str) -> Result<Self, APIError> {
+        let config: AccessConfig =
+            serde_json::from_str(json.trim()).map_err(|err| APIError::InvalidConfig {
+                field: "access_config",
+                message: err.to_string(),
+            })?;
+        self.api_url = Some(config.api_url);
+        self.cert_sha256 = Some(config.cert_sha256);
+        Ok(self)
+    }
+
+    /// Sets the timeout for each API request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for establishing the connection to the server.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Sets the `User-Agent` header sent with every request.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Sends all requests through the given proxy.
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Retries idempotent requests that failed with a transient error according to `policy`.
+    ///
+    /// Requests are sent once unless a policy is set.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Uses a pre-built `reqwest` client instead of building one.
+    ///
+    /// The client is used as is: it must already trust the server certificate, as certificate
+    /// pinning, `connect_timeout`, `user_agent` and `proxy` can only be applied to clients built
+    /// by this builder. Setting them together with a pre-built client is rejected by `build()`.
+    pub fn client(mut self, client: C) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Validates the settings collected so far.
+    pub(crate) fn into_parts(self) -> Result<BuilderParts<C>, APIError> {
+        let api_url = self.api_url.ok_or(APIError::InvalidConfig {
+            field: "api_url",
+            message: "the API URL is required".to_string(),
+        })?;
+        let api_url = parse_api_url(&api_url)?;
+
+        if let Some(cert_sha256) = &self.cert_sha256 {
+            validate_fingerprint(cert_sha256)?;
+        }
+
+        if let Some(retry_policy) = &self.retry_policy {
+            retry_policy.validate()?;
+        }
+
+        if self.client.is_some() {
+            if self.cert_sha256.is_some() {
+                return Err(APIError::InvalidConfig {
+                    field: "client",
+                    message: "the certificate fingerprint can't be pinned on a pre-built client"
+                        .to_string(),
+                });
+            }
+            if self.connect_timeout.is_some() || self.user_agent.is_some() || self.proxy.is_some() {
+                return Err(APIError::InvalidConfig {
+                    field: "client",
+                    message: "connect_timeout, user_agent and proxy can't be applied to a pre-built client".to_string(),
+                });
+            }
+        } else if self.cert_sha256.is_none() {
+            return Err(APIError::InvalidConfig {
+                field: "cert_sha256",
+                message: "the certificate fingerprint is required".to_string(),
+            });
+        }
+
+        Ok(BuilderParts {
+            api_url,
+            cert_sha256: self.cert_sha256,
+            timeout: self.timeout,
+            connect_timeout: self.connect_timeout,
+            user_agent: self.user_agent,
+            proxy: self.proxy,
+            retry_policy: self.retry_policy,
+            client: self.client,
+        })
+    }
+}
+
+fn parse_api_url(api_url: &str) -> Result<Url, APIError> {
+    let invalid = |message: String| APIError::InvalidConfig {
+        field: "api_url",
+        message,
+    };
+    let url = Url::parse(api_url).map_err(|err| invalid(err.to_string()))?;
+    // The certificate pin only protects the secret API path over TLS
+    if url.scheme() != "https" {
+        return Err(invalid(format!(
+            "unsupported scheme `{}`, expected `https`",
+            url.scheme()
+        )));
+    }
+    if url.host().is_none() {
+        return Err(invalid("the URL has no host".to_string()));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(invalid(
+            "the URL must not have a query or a fragment".to_string(),
+        ));
+    }
+    Ok(url)
+}
+
+/// Accepts 64 hex characters, optionally written as 32 colon-separated bytes.
+fn validate_fingerprint(fingerprint: &str) -> Result<(), APIError> {
+    let is_hex_pair = |pair: &str| pair.len() == 2 && pair.chars().all(|c| c.is_ascii_hexdigit());
+    let valid = if fingerprint.contains(':') {
+        let pairs: Vec<&str> = fingerprint.split(':').collect();
+        pairs.len() == 32 && pairs.iter().all(|pair| is_hex_pair(pair))
+    } else {
+        fingerprint.len() == 64 && fingerprint.chars().all(|c| c.is_ascii_hexdigit())
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(APIError::InvalidConfig {
+            field: "cert_sha256",
+            message: "expected 64 hex characters, with or without colons".to_string(),
+        })
+    }
+}
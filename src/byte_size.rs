@@ -0,0 +1,224 @@
+//! Data sizes with decimal and binary units.
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Units accepted by `ByteSize::from_str`, largest first, as used by `Display`.
+const UNITS: [(&str, u64); 8] = [
+    ("TiB", ByteSize::TIB.0),
+    ("TB", ByteSize::TB.0),
+    ("GiB", ByteSize::GIB.0),
+    ("GB", ByteSize::GB.0),
+    ("MiB", ByteSize::MIB.0),
+    ("MB", ByteSize::MB.0),
+    ("KiB", ByteSize::KIB.0),
+    ("KB", ByteSize::KB.0),
+];
+
+/// Fraction digits beyond this can't change the result by a whole byte.
+const MAX_FRACTION_DIGITS: usize = 18;
+
+/// A number of bytes, as used by data limits.
+///
+/// Parses sizes with decimal (`KB`, `MB`, `GB`, `TB`, powers of 1000) or binary (`KiB`, `MiB`,
+/// `GiB`, `TiB`, powers of 1024) units, so that `"10GB"` and `"10GiB"` can't be confused with a
+/// raw number of bytes. Units are case-insensitive and always mean bytes, never bits. Fractions
+/// are rounded down to a whole byte, and sizes that don't fit in a `u64` are rejected.
+///
+/// `Display` writes the largest unit that represents the size exactly, so the output parses back
+/// to the same value. In JSON a size is a number of bytes, as the API expects, but strings with
+/// units are accepted too.
+///
+/// ```rust
+/// use outline_api::ByteSize;
+///
+/// let limit: ByteSize = "10 GiB".parse().unwrap();
+/// assert_eq!(limit.as_u64(), 10 * 1024 * 1024 * 1024);
+/// assert_eq!(limit.to_string(), "10 GiB");
+///
+/// assert_eq!("500MB".parse::<ByteSize>().unwrap(), ByteSize::new(500_000_000));
+/// assert_eq!("1.5 KiB".parse::<ByteSize>().unwrap(), ByteSize::new(1536));
+/// assert!("20000000 TB".parse::<ByteSize>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ByteSize(u64);
+
+impl ByteSize {
+    pub const KB: ByteSize = ByteSize(1000);
+    pub const MB: ByteSize = ByteSize(1000 * 1000);
+    pub const GB: ByteSize = ByteSize(1000 * 1000 * 1000);
+    pub const TB: ByteSize = ByteSize(1000 * 1000 * 1000 * 1000);
+    pub const KIB: ByteSize = ByteSize(1 << 10);
+    pub const MIB: ByteSize = ByteSize(1 << 20);
+    pub const GIB: ByteSize = ByteSize(1 << 30);
+    pub const TIB: ByteSize = ByteSize(1 << 40);
+
+    /// Creates a size from a number of bytes.
+    pub const fn new(bytes: u64) -> Self {
+        ByteSize(bytes)
+    }
+
+    /// Returns the number of bytes.
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// Multiplies the size, e.g. `ByteSize::GIB.checked_mul(10)`, returning `None` on overflow.
+    pub const fn checked_mul(self, factor: u64) -> Option<Self> {
+        match self.0.checked_mul(factor) {
+            Some(bytes) => Some(ByteSize(bytes)),
+            None => None,
+        }
+    }
+}
+
+impl From<u64> for ByteSize {
+    fn from(bytes: u64) -> Self {
+        ByteSize(bytes)
+    }
+}
+
+impl From<&u64> for ByteSize {
+    fn from(bytes: &u64) -> Self {
+        ByteSize(*bytes)
+    }
+}
+
+impl From<ByteSize> for u64 {
+    fn from(size: ByteSize) -> Self {
+        size.0
+    }
+}
+
+impl PartialEq<u64> for ByteSize {
+    fn eq(&self, other: &u64) -> bool {
+        self.0 == *other
+    }
+}
+
+/// Errors returned when parsing a `ByteSize`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ByteSizeError {
+    /// The size doesn't start with a non-negative number.
+    InvalidNumber,
+    /// The unit is not one of `B`, `KB`, `MB`, `GB`, `TB`, `KiB`, `MiB`, `GiB` or `TiB`.
+    UnknownUnit(String),
+    /// The size doesn't fit in a `u64`.
+    Overflow,
+}
+
+impl fmt::Display for ByteSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ByteSizeError::InvalidNumber => f.write_str(
+                "The size must be a non-negative number, optionally followed by a unit.",
+            ),
+            ByteSizeError::UnknownUnit(unit) => write!(
+                f,
+                "Unknown unit `{}`, expected B, KB, MB, GB, TB, KiB, MiB, GiB or TiB.",
+                unit
+            ),
+            ByteSizeError::Overflow => f.write_str("The size is too large."),
+        }
+    }
+}
+
+impl std::error::Error for ByteSizeError {}
+
+impl FromStr for ByteSize {
+    type Err = ByteSizeError;
+
+    fn from_str(size: &str) -> Result<Self, Self::Err> {
+        let size = size.trim();
+        let number_end = size
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(size.len());
+        let (number, unit) = size.split_at(number_end);
+        let unit = unit.trim_start();
+
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        let is_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if whole.is_empty() || !is_digits(fraction) || number.ends_with('.') {
+            return Err(ByteSizeError::InvalidNumber);
+        }
+        let whole = whole.parse::<u64>().map_err(|_| ByteSizeError::Overflow)?;
+
+        let multiplier = if unit.is_empty() || unit.eq_ignore_ascii_case("B") {
+            1
+        } else {
+            UNITS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+                .map(|(_, multiplier)| *multiplier)
+                .ok_or_else(|| ByteSizeError::UnknownUnit(unit.to_string()))?
+        };
+
+        let fraction = &fraction[..fraction.len().min(MAX_FRACTION_DIGITS)];
+        let fraction_bytes = if fraction.is_empty() {
+            0
+        } else {
+            let numerator = fraction
+                .parse::<u128>()
+                .map_err(|_| ByteSizeError::InvalidNumber)?;
+            let denominator = 10u128.pow(fraction.len() as u32);
+            (numerator * u128::from(multiplier) / denominator) as u64
+        };
+
+        whole
+            .checked_mul(multiplier)
+            .and_then(|bytes| bytes.checked_add(fraction_bytes))
+            .map(ByteSize)
+            .ok_or(ByteSizeError::Overflow)
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let unit = UNITS
+            .iter()
+            .find(|(_, multiplier)| self.0 >= *multiplier && self.0 % *multiplier == 0);
+        match unit {
+            Some((name, multiplier)) => write!(f, "{} {}", self.0 / multiplier, name),
+            None => write!(f, "{} B", self.0),
+        }
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ByteSizeVisitor)
+    }
+}
+
+struct ByteSizeVisitor;
+
+impl Visitor<'_> for ByteSizeVisitor {
+    type Value = ByteSize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number of bytes, or a size such as \"10 GiB\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, bytes: u64) -> Result<Self::Value, E> {
+        Ok(ByteSize(bytes))
+    }
+
+    fn visit_i64<E: de::Error>(self, bytes: i64) -> Result<Self::Value, E> {
+        u64::try_from(bytes)
+            .map(ByteSize)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(bytes), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, size: &str) -> Result<Self::Value, E> {
+        size.parse().map_err(E::custom)
+    }
+}
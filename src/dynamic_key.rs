@@ -0,0 +1,141 @@
+//! Dynamic access keys (`ssconf://`).
+
+use crate::{AccessKey, ShadowsocksUrlError};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Config document of an Outline dynamic access key.
+///
+/// Outline clients given an `ssconf://` URL fetch this JSON document over HTTPS and connect with
+/// its settings, so the server behind a key can change without handing out a new key. The
+/// document is generated from an `AccessKey` and can be hosted anywhere; the Outline server isn't
+/// involved.
+///
+/// ```rust
+/// use outline_api::DynamicAccessKey;
+///
+/// let key = DynamicAccessKey {
+///     server: "vpn.example.com".to_string(),
+///     server_port: 4410,
+///     password: "secret".to_string(),
+///     method: "chacha20-ietf-poly1305".to_string(),
+///     prefix: None,
+/// };
+/// assert_eq!(
+///     key.to_json(),
+///     r#"{"server":"vpn.example.com","server_port":4410,"password":"secret","method":"chacha20-ietf-poly1305"}"#
+/// );
+///
+/// let url = DynamicAccessKey::ssconf_url("https://keys.example.com/alice.json", Some("Alice")).unwrap();
+/// assert_eq!(url, "ssconf://keys.example.com/alice.json#Alice");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DynamicAccessKey {
+    /// Hostname or IP address of the server.
+    pub server: String,
+    pub server_port: u16,
+    pub password: String,
+    /// Encryption method, e.g. `chacha20-ietf-poly1305`.
+    pub method: String,
+    /// Bytes sent at the start of each connection to disguise it, e.g. as an HTTP request. Each
+    /// character stands for one byte, so it must only contain code points up to `U+00FF`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+}
+
+/// Errors returned when building a `DynamicAccessKey` or its `ssconf://` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DynamicAccessKeyError {
+    /// The config URL can't be parsed.
+    InvalidConfigUrl,
+    /// The config URL is not `https://`, the only scheme Outline clients fetch dynamic keys from.
+    InsecureConfigUrl,
+    /// The prefix contains a code point above `U+00FF`, which doesn't stand for a single byte.
+    InvalidPrefix,
+}
+
+impl fmt::Display for DynamicAccessKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            DynamicAccessKeyError::InvalidConfigUrl => "The config URL is malformed.",
+            DynamicAccessKeyError::InsecureConfigUrl => {
+                "Dynamic access keys must be served over HTTPS."
+            }
+            DynamicAccessKeyError::InvalidPrefix => {
+                "The prefix must only contain code points up to U+00FF."
+            }
+        })
+    }
+}
+
+impl std::error::Error for DynamicAccessKeyError {}
+
+impl DynamicAccessKey {
+    /// Creates the document of an access key returned by the server.
+    ///
+    /// The server hostname is taken from `access_url`, as `AccessKey` has no separate field for it.
+    pub fn from_access_key(key: &AccessKey) -> Result<Self, ShadowsocksUrlError> {
+        let url = key.shadowsocks_url()?;
+        Ok(DynamicAccessKey {
+            server: url.host().to_string(),
+            server_port: key.port,
+            password: key.password.clone(),
+            method: key.method.clone(),
+            prefix: None,
+        })
+    }
+
+    /// Replaces the server hostname, e.g. to point clients to a new server.
+    pub fn with_server(mut self, server: impl Into<String>) -> Self {
+        self.server = server.into();
+        self
+    }
+
+    /// Sets the connection prefix.
+    ///
+    /// # Errors
+    ///
+    /// - `DynamicAccessKeyError::InvalidPrefix`: If `prefix` contains a code point above `U+00FF`.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Result<Self, DynamicAccessKeyError> {
+        let prefix = prefix.into();
+        if prefix.chars().any(|c| u32::from(c) > 0xff) {
+            return Err(DynamicAccessKeyError::InvalidPrefix);
+        }
+        self.prefix = Some(prefix);
+        Ok(self)
+    }
+
+    /// Serializes the document to JSON, to be served from the URL passed to `ssconf_url`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("dynamic access keys should serialize to JSON")
+    }
+
+    /// Returns the `ssconf://` URL to hand out for a document hosted at `config_url`, optionally
+    /// with a `#name` shown by Outline clients.
+    ///
+    /// # Errors
+    ///
+    /// - `DynamicAccessKeyError::InvalidConfigUrl`: If `config_url` can't be parsed.
+    /// - `DynamicAccessKeyError::InsecureConfigUrl`: If `config_url` is not an `https://` URL, the
+    ///   only scheme Outline clients fetch dynamic keys from.
+    pub fn ssconf_url(
+        config_url: &str,
+        name: Option<&str>,
+    ) -> Result<String, DynamicAccessKeyError> {
+        let mut url =
+            reqwest::Url::parse(config_url).map_err(|_| DynamicAccessKeyError::InvalidConfigUrl)?;
+        if url.scheme() != "https" {
+            return Err(DynamicAccessKeyError::InsecureConfigUrl);
+        }
+
+        url.set_fragment(None);
+        let mut ssconf_url = url.as_str().replacen("https://", "ssconf://", 1);
+        if let Some(name) = name {
+            ssconf_url.push('#');
+            ssconf_url.extend(utf8_percent_encode(name, NON_ALPHANUMERIC));
+        }
+        Ok(ssconf_url)
+    }
+}
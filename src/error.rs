@@ -0,0 +1,194 @@
+use reqwest::StatusCode;
+use std::fmt;
+
+/// Details of a response the server answered with an unexpected status or body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorResponse {
+    /// HTTP status code returned by the server.
+    pub status: StatusCode,
+    /// API path the request was sent to, without the secret URL prefix (e.g. `/access-keys/1/name`).
+    pub endpoint: String,
+    /// Raw response body, usually a JSON object with `code` and `message` fields.
+    pub body: String,
+}
+
+/// Errors returned by the Outline VPN Management API client.
+///
+/// Variants that correspond to an HTTP error status carry the [`ErrorResponse`] received
+/// from the server, so callers can match on the kind of failure without parsing messages.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum APIError {
+    /// The client configuration passed to `OutlineVPN::builder()` is invalid.
+    InvalidConfig {
+        field: &'static str,
+        message: String,
+    },
+    /// An argument was rejected before sending the request, e.g. port `0`.
+    InvalidArgument { name: &'static str, message: String },
+    /// The underlying `reqwest` client could not be built.
+    ClientBuild(reqwest::Error),
+    /// The request could not be sent or its response could not be read (DNS, connection,
+    /// TLS, timeout, ...). Inspect `source` for details.
+    Transport {
+        endpoint: String,
+        source: reqwest::Error,
+    },
+    /// The server presented a TLS certificate whose SHA-256 fingerprint doesn't match the pinned one.
+    CertificatePinMismatch {
+        endpoint: String,
+        expected: String,
+        actual: String,
+    },
+    /// The server answered successfully, but the body didn't match the expected schema.
+    InvalidResponse {
+        response: ErrorResponse,
+        source: serde_json::Error,
+    },
+    /// `500` – An internal error occurred.
+    InternalError(ErrorResponse),
+    /// `400` – An invalid hostname or IP address was provided.
+    InvalidHostname(ErrorResponse),
+    /// `400` – The requested port wasn't an integer from 1 through 65535.
+    InvalidPort(ErrorResponse),
+    /// `409` – The requested port was already in use by another service.
+    PortConflict(ErrorResponse),
+    /// `400` – Invalid data limit.
+    InvalidDataLimit(ErrorResponse),
+    /// `409` – An access key with the requested ID already exists.
+    AccessKeyAlreadyExists(ErrorResponse),
+    /// `404` – Access key inexistent.
+    AccessKeyInexistent(ErrorResponse),
+    /// `400` – Invalid name.
+    InvalidName(ErrorResponse),
+    /// `400` – Invalid request.
+    InvalidRequest(ErrorResponse),
+    /// Any other status code.
+    UnknownError(ErrorResponse),
+}
+
+impl APIError {
+    /// Wraps a `reqwest::Error`, recognizing certificate pinning failures.
+    pub(crate) fn from_transport(endpoint: &str, source: reqwest::Error) -> Self {
+        match crate::tls::find_pin_mismatch(&source) {
+            Some(mismatch) => APIError::CertificatePinMismatch {
+                endpoint: endpoint.to_string(),
+                expected: mismatch.expected.clone(),
+                actual: mismatch.actual.clone(),
+            },
+            None => APIError::Transport {
+                endpoint: endpoint.to_string(),
+                source,
+            },
+        }
+    }
+
+    /// Returns the server response associated with this error, if the server answered at all.
+    pub fn response(&self) -> Option<&ErrorResponse> {
+        match self {
+            APIError::InvalidConfig { .. }
+            | APIError::InvalidArgument { .. }
+            | APIError::ClientBuild(_)
+            | APIError::Transport { .. }
+            | APIError::CertificatePinMismatch { .. } => None,
+            APIError::InvalidResponse { response, .. }
+            | APIError::InternalError(response)
+            | APIError::InvalidHostname(response)
+            | APIError::InvalidPort(response)
+            | APIError::PortConflict(response)
+            | APIError::InvalidDataLimit(response)
+            | APIError::AccessKeyAlreadyExists(response)
+            | APIError::AccessKeyInexistent(response)
+            | APIError::InvalidName(response)
+            | APIError::InvalidRequest(response)
+            | APIError::UnknownError(response) => Some(response),
+        }
+    }
+
+    /// Returns the HTTP status code, if the server answered at all.
+    pub fn status(&self) -> Option<StatusCode> {
+        self.response().map(|response| response.status)
+    }
+
+    /// Returns the API path the failed request was sent to, or an empty string if the error
+    /// isn't related to a request.
+    pub fn endpoint(&self) -> &str {
+        match self {
+            APIError::Transport { endpoint, .. }
+            | APIError::CertificatePinMismatch { endpoint, .. } => endpoint,
+            _ => self
+                .response()
+                .map(|response| response.endpoint.as_str())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Returns the raw response body, if the server answered at all.
+    pub fn body(&self) -> Option<&str> {
+        self.response().map(|response| response.body.as_str())
+    }
+
+    /// Returns `true` if the request failed because it timed out.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, APIError::Transport { source, .. } if source.is_timeout())
+    }
+}
+
+impl fmt::Display for APIError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            APIError::InvalidConfig { field, message } => {
+                return write!(f, "Invalid `{}`: {}", field, message)
+            }
+            APIError::InvalidArgument { name, message } => {
+                return write!(f, "Invalid argument `{}`: {}", name, message)
+            }
+            APIError::ClientBuild(source) => {
+                return write!(f, "Failed to build the HTTP client: {}", source)
+            }
+            APIError::Transport { endpoint, source } => {
+                return write!(f, "Request to {} failed: {}", endpoint, source)
+            }
+            APIError::CertificatePinMismatch {
+                expected, actual, ..
+            } => {
+                return write!(
+                    f,
+                    "The server certificate SHA-256 {} doesn't match the pinned fingerprint {}.",
+                    actual, expected
+                )
+            }
+            APIError::InvalidResponse { .. } => "Error deserializing JSON.",
+            APIError::InternalError(_) => "An internal error occurred.",
+            APIError::InvalidHostname(_) => "An invalid hostname or IP address was provided.",
+            APIError::InvalidPort(_) => "The requested port wasn't an integer from 1 through 65535, or the request had no port parameter.",
+            APIError::PortConflict(_) => "The requested port was already in use by another service.",
+            APIError::InvalidDataLimit(_) => "Invalid data limit.",
+            APIError::AccessKeyAlreadyExists(_) => "An access key with this ID already exists.",
+            APIError::AccessKeyInexistent(_) => "Access key inexistent.",
+            APIError::InvalidName(_) => "Invalid name.",
+            APIError::InvalidRequest(_) => "Invalid request.",
+            APIError::UnknownError(_) => "An unknown error occurred.",
+        };
+
+        match self.response() {
+            Some(response) => write!(
+                f,
+                "{} ({} at {})",
+                message, response.status, response.endpoint
+            ),
+            None => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for APIError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            APIError::ClientBuild(source) => Some(source),
+            APIError::Transport { source, .. } => Some(source),
+            APIError::InvalidResponse { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,285 @@
+//! Prometheus exporter for access key metrics.
+
+use crate::blocking::OutlineVPN;
+use crate::listener::{read_request, Listener};
+use crate::{APIError, AccessKey, AccessKeyId, DataLimit, Fleet, FleetResults};
+use log::warn;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+/// Largest request head accepted from a scraper.
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// Content type of the Prometheus text exposition format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Polls the servers of a `Fleet` and serves their metrics in the Prometheus text format.
+///
+/// Every poll reads the server information, access keys and their transferred bytes on each
+/// server, and exposes:
+///
+/// - `outline_scrape_success{server}`: `1` if the server answered every request, `0` otherwise.
+/// - `outline_access_keys{server}`: number of access keys.
+/// - `outline_key_bytes_transferred{server,key_id,key_name}`: bytes transferred by each key, as
+///   reported by `GET /metrics/transfer`.
+/// - `outline_key_data_limit_bytes{server,key_id,key_name}`: data limit of each key that has one,
+///   either its own or the server-wide `accessKeyDataLimit`.
+///
+/// Key metrics of a server that failed are left out rather than reported as stale values.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use outline_api::{Exporter, Fleet, OutlineVPN};
+/// use std::time::Duration;
+///
+/// let vpn = OutlineVPN::builder()
+///     .access_config(r#"{"apiUrl":"https://1.2.3.4:1234/secret","certSha256":"E2DE8E9A0A5BD9E8B3A8A5A9D5C8F8E2DE8E9A0A5BD9E8B3A8A5A9D5C8F82A75"}"#)?
+///     .build()?;
+///
+/// // Serves http://127.0.0.1:9800/metrics until the process exits
+/// Exporter::new(Fleet::new().with_server("eu-1", vpn))
+///     .interval(Duration::from_secs(30))
+///     .serve("127.0.0.1:9800")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Exporter {
+    fleet: Fleet,
+    interval: Duration,
+}
+
+impl Exporter {
+    /// Creates an exporter polling the servers every 60 seconds.
+    pub fn new(fleet: Fleet) -> Self {
+        Exporter {
+            fleet,
+            interval: Duration::from_secs(60),
+        }
+    }
+
+    /// Sets the time between polls, which must not be zero.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Polls every server once and returns the metrics in the Prometheus text format.
+    pub fn collect(&self) -> String {
+        let samples = self.fleet.for_each(sample);
+        for (server, result) in &samples {
+            if let Err(err) = result {
+                warn!("Can't collect the metrics of {}: {}", server, err);
+            }
+        }
+
+        let mut output = String::new();
+        family(
+            &mut output,
+            "outline_scrape_success",
+            "Whether the last poll of the server succeeded.",
+        );
+        for (server, result) in &samples {
+            let success = u64::from(result.is_ok());
+            line(&mut output, "outline_scrape_success", server, None, success);
+        }
+
+        family(&mut output, "outline_access_keys", "Number of access keys.");
+        for (server, sample) in successful(&samples) {
+            let count = sample.keys.len() as u64;
+            line(&mut output, "outline_access_keys", server, None, count);
+        }
+
+        family(
+            &mut output,
+            "outline_key_bytes_transferred",
+            "Bytes transferred by the access key, as reported by the server.",
+        );
+        for (server, sample) in successful(&samples) {
+            for key in &sample.keys {
+                // Keys that haven't transferred anything yet are missing from the server response
+                let bytes = sample.transferred.get(&key.id).copied().unwrap_or(0);
+                line(
+                    &mut output,
+                    "outline_key_bytes_transferred",
+                    server,
+                    Some(key),
+                    bytes,
+                );
+            }
+        }
+
+        family(
+            &mut output,
+            "outline_key_data_limit_bytes",
+            "Data transfer limit of the access key.",
+        );
+        for (server, sample) in successful(&samples) {
+            for key in &sample.keys {
+                if let Some(limit) = key.data_limit.or(sample.server_limit) {
+                    line(
+                        &mut output,
+                        "outline_key_data_limit_bytes",
+                        server,
+                        Some(key),
+                        limit.bytes.as_u64(),
+                    );
+                }
+            }
+        }
+
+        output
+    }
+
+    /// Starts polling and serving the metrics on `address` from background threads.
+    ///
+    /// The first poll completes before this returns, so scrapers never see an empty page. The
+    /// metrics are served at `/metrics`; the threads stop when the returned handle is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the interval is zero or `address` can't be bound.
+    pub fn spawn(self, address: impl ToSocketAddrs) -> io::Result<ExporterHandle> {
+        if self.interval.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the interval between polls must not be zero",
+            ));
+        }
+        let listener = TcpListener::bind(address)?;
+        let metrics = Arc::new(RwLock::new(self.collect()));
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let poll_metrics = Arc::clone(&metrics);
+        thread::spawn(move || {
+            // Waits for the next poll, or for the handle to be dropped
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(self.interval) {
+                let collected = self.collect();
+                *poll_metrics.write().unwrap_or_else(|err| err.into_inner()) = collected;
+            }
+        });
+
+        let listener = Listener::spawn(listener, move |socket| serve_connection(socket, &metrics))?;
+
+        Ok(ExporterHandle {
+            listener,
+            _stop: stop,
+        })
+    }
+
+    /// Polls and serves the metrics on `address`, blocking the current thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the interval is zero or `address` can't be bound.
+    pub fn serve(self, address: impl ToSocketAddrs) -> io::Result<()> {
+        self.spawn(address)?.listener.join();
+        Ok(())
+    }
+}
+
+/// A running `Exporter`, stopped when dropped.
+#[derive(Debug)]
+pub struct ExporterHandle {
+    listener: Listener,
+    /// Dropping the sender wakes up the polling thread so it stops.
+    _stop: mpsc::Sender<()>,
+}
+
+impl ExporterHandle {
+    /// Returns the address the metrics are served on, e.g. when bound to port `0`.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener.local_addr()
+    }
+}
+
+/// Keys, transferred bytes and server-wide data limit of one server.
+struct Sample {
+    server_limit: Option<DataLimit>,
+    keys: Vec<AccessKey>,
+    transferred: HashMap<AccessKeyId, u64>,
+}
+
+fn sample(vpn: &OutlineVPN) -> Result<Sample, APIError> {
+    Ok(Sample {
+        server_limit: vpn.get_server_info()?.access_key_data_limit,
+        keys: vpn.list_access_keys()?.access_keys,
+        transferred: vpn
+            .get_each_access_key_data_transferred()?
+            .bytes_transferred_by_user_id,
+    })
+}
+
+fn successful(samples: &FleetResults<Sample>) -> impl Iterator<Item = (&String, &Sample)> {
+    samples
+        .iter()
+        .filter_map(|(server, result)| Some((server, result.as_ref().ok()?)))
+}
+
+fn family(output: &mut String, name: &str, help: &str) {
+    let _ = writeln!(output, "# HELP {} {}", name, help);
+    let _ = writeln!(output, "# TYPE {} gauge", name);
+}
+
+fn line(output: &mut String, name: &str, server: &str, key: Option<&AccessKey>, value: u64) {
+    let _ = write!(output, "{}{{server=\"{}\"", name, escape(server));
+    if let Some(key) = key {
+        let _ = write!(
+            output,
+            ",key_id=\"{}\",key_name=\"{}\"",
+            escape(key.id.as_str()),
+            escape(&key.name)
+        );
+    }
+    let _ = writeln!(output, "}} {}", value);
+}
+
+/// Escapes a label value as required by the text format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Answers a single scrape, then closes the connection.
+fn serve_connection(mut socket: TcpStream, metrics: &RwLock<String>) -> io::Result<()> {
+    socket.set_read_timeout(Some(Duration::from_secs(10)))?;
+
+    let Some(request) = read_request(&mut socket, MAX_REQUEST_SIZE)? else {
+        return Ok(());
+    };
+    let method = request.method.as_str();
+    let path = request.target.split('?').next().unwrap_or_default();
+
+    let (status, body) = match (method, path) {
+        ("GET" | "HEAD", "/metrics") => (
+            "200 OK",
+            metrics
+                .read()
+                .unwrap_or_else(|err| err.into_inner())
+                .clone(),
+        ),
+        (_, "/metrics") => ("405 Method Not Allowed", String::new()),
+        _ => ("404 Not Found", String::new()),
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        CONTENT_TYPE,
+        body.len()
+    );
+    socket.write_all(head.as_bytes())?;
+    if method != "HEAD" {
+        socket.write_all(body.as_bytes())?;
+    }
+    socket.flush()
+}
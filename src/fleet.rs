@@ -0,0 +1,160 @@
+//! Operations spanning several Outline servers.
+
+use crate::blocking::OutlineVPN;
+use crate::{APIError, AccessKeyList, ByteSize, Server, TransferMetrics};
+use std::collections::BTreeMap;
+use std::thread;
+
+/// Results of a fleet operation, keyed by server name.
+///
+/// Every server has its own entry, so a failure on one server doesn't hide the results of the
+/// others.
+pub type FleetResults<T> = BTreeMap<String, Result<T, APIError>>;
+
+/// A set of named `OutlineVPN` clients, one per server.
+///
+/// Operations are sent to all servers concurrently, each from its own thread, and return once
+/// every server has answered or failed.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # fn main() -> Result<(), outline_api::APIError> {
+/// use outline_api::{Fleet, OutlineVPN};
+///
+/// let connect = |access_config: &str| OutlineVPN::builder().access_config(access_config)?.build();
+///
+/// let fleet = Fleet::new()
+///     .with_server("eu-1", connect(r#"{"apiUrl":"https://1.2.3.4:1234/secret","certSha256":"E2DE8E9A0A5BD9E8B3A8A5A9D5C8F8E2DE8E9A0A5BD9E8B3A8A5A9D5C8F82A75"}"#)?)
+///     .with_server("us-1", connect(r#"{"apiUrl":"https://5.6.7.8:1234/secret","certSha256":"E2DE8E9A0A5BD9E8B3A8A5A9D5C8F8E2DE8E9A0A5BD9E8B3A8A5A9D5C8F82A75"}"#)?);
+///
+/// for (server, result) in fleet.list_access_keys() {
+///     match result {
+///         Ok(list) => println!("{}: {} keys", server, list.access_keys.len()),
+///         Err(err) => eprintln!("{}: {}", server, err),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Fleet {
+    servers: BTreeMap<String, OutlineVPN>,
+}
+
+impl Fleet {
+    /// Creates an empty fleet.
+    pub fn new() -> Self {
+        Fleet::default()
+    }
+
+    /// Adds a server, replacing any server with the same name.
+    pub fn with_server(mut self, name: impl Into<String>, client: OutlineVPN) -> Self {
+        self.insert(name, client);
+        self
+    }
+
+    /// Adds a server and returns the client previously registered under `name`, if any.
+    pub fn insert(&mut self, name: impl Into<String>, client: OutlineVPN) -> Option<OutlineVPN> {
+        self.servers.insert(name.into(), client)
+    }
+
+    /// Removes a server and returns its client.
+    pub fn remove(&mut self, name: &str) -> Option<OutlineVPN> {
+        self.servers.remove(name)
+    }
+
+    /// Returns the client of a server.
+    pub fn get(&self, name: &str) -> Option<&OutlineVPN> {
+        self.servers.get(name)
+    }
+
+    /// Returns the server names, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.servers.keys().map(String::as_str)
+    }
+
+    /// Returns the number of servers.
+    pub fn len(&self) -> usize {
+        self.servers.len()
+    }
+
+    /// Returns `true` if the fleet has no servers.
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+
+    /// Runs `operation` against every server concurrently and collects the results.
+    ///
+    /// A panic in `operation` is propagated once all servers are done.
+    pub fn for_each<T, F>(&self, operation: F) -> FleetResults<T>
+    where
+        T: Send,
+        F: Fn(&OutlineVPN) -> Result<T, APIError> + Sync,
+    {
+        let operation = &operation;
+        thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .servers
+                .iter()
+                .map(|(name, client)| (name, scope.spawn(move || operation(client))))
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|(name, handle)| match handle.join() {
+                    Ok(result) => (name.clone(), result),
+                    Err(panic) => std::panic::resume_unwind(panic),
+                })
+                .collect()
+        })
+    }
+
+    /// Gets the information of every server.
+    pub fn get_server_info(&self) -> FleetResults<Server> {
+        self.for_each(OutlineVPN::get_server_info)
+    }
+
+    /// Lists the access keys of every server.
+    pub fn list_access_keys(&self) -> FleetResults<AccessKeyList> {
+        self.for_each(OutlineVPN::list_access_keys)
+    }
+
+    /// Gets the data transferred by each access key of every server.
+    pub fn get_each_access_key_data_transferred(&self) -> FleetResults<TransferMetrics> {
+        self.for_each(OutlineVPN::get_each_access_key_data_transferred)
+    }
+
+    /// Sets the data transfer limit for all access keys of every server.
+    pub fn set_data_transfer_limit_for_all_access_keys(
+        &self,
+        limit: impl Into<ByteSize>,
+    ) -> FleetResults<()> {
+        let limit = limit.into();
+        self.for_each(|client| client.set_data_transfer_limit_for_all_access_keys(limit))
+    }
+
+    /// Removes the data limit for all access keys of every server.
+    pub fn remove_data_limit_for_all_access_keys(&self) -> FleetResults<()> {
+        self.for_each(OutlineVPN::remove_data_limit_for_all_access_keys)
+    }
+}
+
+impl<N: Into<String>> FromIterator<(N, OutlineVPN)> for Fleet {
+    fn from_iter<I: IntoIterator<Item = (N, OutlineVPN)>>(servers: I) -> Self {
+        Fleet {
+            servers: servers
+                .into_iter()
+                .map(|(name, client)| (name.into(), client))
+                .collect(),
+        }
+    }
+}
+
+impl<N: Into<String>> Extend<(N, OutlineVPN)> for Fleet {
+    fn extend<I: IntoIterator<Item = (N, OutlineVPN)>>(&mut self, servers: I) {
+        for (name, client) in servers {
+            self.insert(name, client);
+        }
+    }
+}
@@ -1,19 +1,86 @@
 //! This package implements [OutlineVPN](https://getoutline.org) Management API.
-
-use log::debug;
-use reqwest::blocking::{Client, Response};
-use reqwest::header::HeaderMap;
-use std::time::Duration;
+//!
+//! The blocking client (`blocking` feature, enabled by default) is re-exported at the crate root.
+//! The async client lives in the `asynchronous` module and requires the `async` feature.
+
+// Without any client enabled the shared response handling is unused
+#![cfg_attr(
+    not(any(feature = "blocking", feature = "async")),
+    allow(dead_code, unused_imports)
+)]
+
+use reqwest::{StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::num::NonZeroU16;
 
 extern crate serde_json;
 
+mod alerts;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod builder;
+mod byte_size;
+mod dynamic_key;
+mod error;
+#[cfg(feature = "blocking")]
+mod exporter;
+#[cfg(feature = "blocking")]
+mod fleet;
+#[cfg(any(feature = "blocking", feature = "test-util"))]
+mod listener;
+#[cfg(feature = "test-util")]
+pub mod mock;
+mod models;
+#[cfg(feature = "qr")]
+mod qr;
+mod quota;
+mod reconcile;
+mod recorder;
+mod retry;
+mod shadowsocks;
+mod tls;
+
+use models::{DataLimitRequest, HostnameRequest, MetricsEnabledRequest, NameRequest, PortRequest};
+
+#[cfg(feature = "blocking")]
+#[allow(deprecated)]
+pub use blocking::{new, OutlineVPN};
+#[cfg(feature = "blocking")]
+pub use exporter::{Exporter, ExporterHandle};
+#[cfg(feature = "blocking")]
+pub use fleet::{Fleet, FleetResults};
+
+#[cfg(feature = "webhook")]
+pub use alerts::WebhookSink;
+pub use alerts::{AlertSink, SinkError, StdoutSink, UsageAlert, UsageWatcher};
+pub use builder::OutlineVPNBuilder;
+pub use byte_size::{ByteSize, ByteSizeError};
+pub use dynamic_key::{DynamicAccessKey, DynamicAccessKeyError};
+pub use error::{APIError, ErrorResponse};
+pub use models::{
+    AccessKey, AccessKeyId, AccessKeyList, CreateAccessKeyRequest, DataLimit, ExtraFields,
+    MetricsEnabled, Server, TransferMetrics,
+};
+#[cfg(feature = "qr")]
+pub use qr::{QrCode, QrError};
+pub use quota::{Quota, QuotaEngine, QuotaError};
+pub use reconcile::{DesiredKey, Operation, Outcome, Plan, ReconcileError, Reconciler};
+pub use recorder::{Recorder, Snapshot};
+pub use retry::RetryPolicy;
+pub use shadowsocks::{ShadowsocksUrl, ShadowsocksUrlError};
+
 // API reference v1.0
 // See api.yml at project github or
 // https://github.com/Jigsaw-Code/outline-server/blob/1ac9f238132d5917b42d4b6615727e477aa7bbc0/src/shadowbox/server/api.yml
 
 // API documentation is, hmm... discussable
-// List of actually unavailable methods:
-//   get_access_key_by_id
+// List of methods that are unavailable on some server versions:
+//   get_access_key_by_id (falls back to list_access_keys)
 
 /// Configures the logging system based on the build mode.
 ///
@@ -36,497 +103,204 @@ pub fn configure_logging() {
     }
 }
 
-#[derive(Debug)]
-enum APIError {
-    UnknownServerError,
-    InternalError,
-    InvalidHostname,
-    InvalidPort,
-    PortConflict,
-    InvalidDataLimit,
-    AccessKeyInexistent,
-    InvalidName,
-    InvalidRequest,
-    UnknownError,
+// Endpoints
+pub(crate) const NAME_ENDPOINT: &str = "/name";
+pub(crate) const SERVER_ENDPOINT: &str = "/server";
+pub(crate) const HOSTNAME_ENDPOINT: &str = "/server/hostname-for-access-keys";
+pub(crate) const CHANGE_PORT_ENDPOINT: &str = "/server/port-for-new-access-keys";
+pub(crate) const KEY_DATA_LIMIT_ENDPOINT: &str = "/server/access-key-data-limit";
+pub(crate) const METRICS_ENDPOINT: &str = "/metrics";
+pub(crate) const ACCESS_KEYS_ENDPOINT: &str = "/access-keys";
+
+/// Builds the URL of an API endpoint by appending `api_path` to the API URL.
+pub(crate) fn join_api_url(api_url: &Url, api_path: &str) -> String {
+    format!("{}{}", api_url.as_str().trim_end_matches('/'), api_path)
+}
+
+/// Returns the API URL without its path, which holds the secret that grants access to the API.
+pub(crate) fn redact_api_url(api_url: &Url) -> String {
+    format!("{}/<redacted>", api_url.origin().ascii_serialization())
 }
 
-// Endpoints
-const NAME_ENDPOINT: &str = "/name";
-const SERVER_ENDPOINT: &str = "/server";
-const HOSTNAME_ENDPOINT: &str = "/server/hostname-for-access-keys";
-const CHANGE_PORT_ENDPOINT: &str = "/server/port-for-new-access-keys";
-const KEY_DATA_LIMIT_ENDPOINT: &str = "/server/access-key-data-limit";
-const METRICS_ENDPOINT: &str = "/metrics";
-const ACCESS_KEYS_ENDPOINT: &str = "/access-keys";
+/// Builds the path of an access key resource, e.g. `/access-keys/{id}/name` for `suffix` `/name`.
+pub(crate) fn access_key_path(id: &AccessKeyId, suffix: &str) -> String {
+    format!(
+        "{}/{}{}",
+        ACCESS_KEYS_ENDPOINT,
+        id.to_path_segment(),
+        suffix
+    )
+}
+
+/// Returns `true` for `/access-keys/{id}`, but not for the collection or per-key sub-resources.
+pub(crate) fn is_access_key_path(api_path: &str) -> bool {
+    api_path
+        .strip_prefix(ACCESS_KEYS_ENDPOINT)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .is_some_and(|id| !id.is_empty() && !id.contains('/'))
+}
 
-impl std::fmt::Display for APIError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            APIError::UnknownServerError => write!(f, "An unknown server error occurred."),
-            APIError::InternalError => write!(f, "An internal error occurred."),
-            APIError::InvalidHostname => write!(f, "An invalid hostname or IP address was provided."),
-            APIError::InvalidPort => write!(f, "The requested port wasn't an integer from 1 through 65535, or the request had no port parameter."),
-            APIError::PortConflict => write!(f, "The requested port was already in use by another service."),
-            APIError::InvalidDataLimit => write!(f, "Invalid data limit."),
-            APIError::AccessKeyInexistent => write!(f, "Access key inexistent."),
-            APIError::InvalidName => write!(f, "Invalid name."),
-            APIError::InvalidRequest => write!(f, "Invalid request."),
-            APIError::UnknownError => write!(f, "An unknown error occurred."),
+/// Returns `true` if the server doesn't implement the requested route, as opposed to answering
+/// that the requested resource doesn't exist.
+///
+/// Older servers don't implement `GET /access-keys/{id}`: the router answers either `405` (the
+/// path exists for other methods) or `404` with a `ResourceNotFound` code, while a missing key is
+/// reported as `404` with a `NotFound`/`NotFoundError` code.
+pub(crate) fn is_unsupported_route(response: &RawResponse) -> bool {
+    match response.status {
+        StatusCode::METHOD_NOT_ALLOWED => true,
+        StatusCode::NOT_FOUND => {
+            let code = serde_json::from_str::<serde_json::Value>(&response.body)
+                .ok()
+                .and_then(|body| body.get("code")?.as_str().map(str::to_string));
+            !matches!(code.as_deref(), Some("NotFound") | Some("NotFoundError"))
         }
+        _ => false,
     }
 }
 
-/// Handles API responses and returns a result with either a JSON value or an error message.
+/// Checks that `port` is a TCP port from 1 through 65535 before it's sent to the server.
+pub(crate) fn validate_port(port: impl TryInto<NonZeroU16>) -> Result<NonZeroU16, APIError> {
+    port.try_into().map_err(|_| APIError::InvalidArgument {
+        name: "port",
+        message: "expected an integer from 1 through 65535".to_string(),
+    })
+}
+
+/// Returns a random number without pulling in an RNG: every `RandomState` is seeded randomly.
+pub(crate) fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// Serializes a request body to JSON.
+pub(crate) fn json_body<T: Serialize>(body: &T) -> String {
+    // Request bodies are plain structs with string keys, so serialization can't fail
+    serde_json::to_string(body).expect("request body should serialize to JSON")
+}
+
+/// Status code and body of an API response.
+///
+/// Both the blocking and the async client read the whole response into this struct, so the
+/// response handling below is shared between them.
+pub(crate) struct RawResponse {
+    pub(crate) status: StatusCode,
+    pub(crate) body: String,
+}
+
+/// Handles API responses and returns a result with either a deserialized value or an error.
 ///
 /// This function processes the response from an API request and checks the status code to determine
-/// the outcome. If the response status code is `200 OK`, it attempts to deserialize the response body
-/// as JSON and returns the JSON value. If the status code is `500 Internal Server Error`, it returns
-/// an error indicating an internal server error. For all other status codes, it returns an unknown
-/// error message.
+/// the outcome. If the response status code is `200 OK` or `201 Created`, it attempts to deserialize
+/// the response body as JSON into `T` and returns it. Any other status code is mapped to an error by
+/// `api_error`.
 ///
 /// # Arguments
 ///
-/// - `response`: The `Response` object received from the API request.
+/// - `response`: The `RawResponse` received from the API request.
+/// - `api_path`: The API endpoint path the request was sent to.
 ///
 /// # Returns
 ///
 /// Returns a `Result` where:
-/// - `Ok(json_value)` contains the deserialized JSON value if the response status code is `200 OK`.
-/// - `Err(error_message)` contains an error message if the response status code is not `200 OK`.
+/// - `Ok(value)` contains the deserialized value if the response status code is `200 OK` or `201 Created`.
+/// - `Err(error)` contains an `APIError` otherwise.
 ///
 /// # Errors
 ///
 /// This function can return the following errors:
 ///
-/// - `APIError::InternalError`: If the response status code is `500 Internal Server Error`, indicating
-///   an internal server error.
-/// - `APIError::UnknownError`: If the response status code is not `200 OK` or `500 Internal Server Error`,
-///   indicating an unknown error occurred.
-fn handle_json_api_result(response: Response) -> Result<serde_json::Value, String> {
-    match response.status() {
-        reqwest::StatusCode::OK => {
-            let response_body = response
-                .text()
-                .map_err(|_| "Error reading response body".to_string())?;
-            let json_value: serde_json::Value = serde_json::from_str(&response_body)
-                .map_err(|_| "Error deserializing JSON".to_string())?;
-            Ok(json_value)
+/// - `APIError::InvalidResponse`: If the response body doesn't match the expected schema.
+/// - Any error returned by `api_error` for a non-successful status code.
+pub(crate) fn handle_json_api_result<T: DeserializeOwned>(
+    response: RawResponse,
+    api_path: &str,
+) -> Result<T, APIError> {
+    match response.status {
+        StatusCode::OK | StatusCode::CREATED => {
+            serde_json::from_str(&response.body).map_err(|source| APIError::InvalidResponse {
+                response: ErrorResponse {
+                    status: response.status,
+                    endpoint: api_path.to_string(),
+                    body: response.body,
+                },
+                source,
+            })
         }
-        reqwest::StatusCode::INTERNAL_SERVER_ERROR => Err(APIError::InternalError.to_string()),
-        _ => Err(APIError::UnknownError.to_string()),
+        _ => Err(api_error(response, api_path)),
     }
 }
 
 /// Handles the HTTP response status for various API requests.
 ///
-/// This function checks the HTTP response status code from a `reqwest::Response` object
+/// This function checks the HTTP response status code from a `RawResponse`
 /// and maps it to an appropriate `Result` type. It handles various standard HTTP status
 /// codes, such as OK, NO_CONTENT, BAD_REQUEST, CONFLICT, NOT_FOUND, and INTERNAL_SERVER_ERROR.
-/// Depending on the API endpoint and the specific error, it returns either an `Ok(())` for
-/// successful responses or an `Err(String)` for errors, with a descriptive error message.
+/// It returns either an `Ok(())` for successful responses or an `Err(APIError)` built by
+/// `api_error`, carrying the status, endpoint and response body.
 ///
 /// # Arguments
 ///
-/// * `response` – A reference to the `reqwest::Response` object obtained from an API call.
+/// * `response` – The `RawResponse` obtained from an API call.
 /// * `api_path` – A string slice that holds the API endpoint path.
 ///
 /// # Returns
 ///
-/// This function returns a `Result<(), String>`. On successful response status, it returns `Ok(())`.
-/// On error, it returns `Err(String)` with an appropriate error message based on the API endpoint
-/// and the response status code.
+/// This function returns a `Result<(), APIError>`. On successful response status, it returns `Ok(())`.
+/// On error, it returns `Err(APIError)` with a variant chosen based on the API endpoint and the
+/// response status code.
 ///
 /// # Error Handling
 ///
-/// This function handles the following `reqwest::StatusCode` variants:
+/// This function handles the following `StatusCode` variants:
 /// - `OK`: Indicates a successful request.
 /// - `NO_CONTENT`: Indicates a successful request with no content to return.
 /// - `BAD_REQUEST`: Maps to specific API errors based on the `api_path`.
-/// - `CONFLICT`: Indicates a port conflict error.
+/// - `CONFLICT`: Indicates a port conflict error, or an existing key for `/access-keys/{id}`.
 /// - `NOT_FOUND`: Indicates an invalid access key error.
 /// - `INTERNAL_SERVER_ERROR`: Indicates an internal server error.
 /// - Any other status codes are mapped to an unknown error.
-///
-/// Specific errors are derived from the `APIError` enum, translating enum variants to strings.
-fn handle_response_status(response: &Response, api_path: &str) -> Result<(), String> {
-    match response.status() {
-        reqwest::StatusCode::OK => Ok(()),
-        reqwest::StatusCode::NO_CONTENT => Ok(()),
-        reqwest::StatusCode::BAD_REQUEST => match api_path {
-            NAME_ENDPOINT => Err(APIError::InvalidName.to_string()),
-            HOSTNAME_ENDPOINT => Err(APIError::InvalidHostname.to_string()),
-            CHANGE_PORT_ENDPOINT => Err(APIError::InvalidPort.to_string()),
-            KEY_DATA_LIMIT_ENDPOINT | ACCESS_KEYS_ENDPOINT => {
-                Err(APIError::InvalidDataLimit.to_string())
-            }
-            _ => Err(APIError::InvalidRequest.to_string()),
-        },
-        reqwest::StatusCode::CONFLICT => Err(APIError::PortConflict.to_string()),
-        reqwest::StatusCode::NOT_FOUND => Err(APIError::AccessKeyInexistent.to_string()),
-        reqwest::StatusCode::INTERNAL_SERVER_ERROR => Err(APIError::InternalError.to_string()),
-        _ => Err(APIError::UnknownError.to_string()),
-    }
-}
-
-/// Represents a client for interacting with the Outline VPN Server API.
-///
-/// The `OutlineVPN` struct provides methods to perform various operations on the Outline VPN server
-/// such as retrieving server information, changing settings, creating access keys, and more.
-///
-/// # Fields
-///
-/// - `api_url`: A reference to a string representing the URL (including `secret`) of the Outline VPN server API.
-/// - `session`: A reqwest HTTP client used to make API requests.
-/// - `request_timeout_in_sec`: The time to set the timeout for API requests.
-pub struct OutlineVPN<'a> {
-    api_url: &'a str,
-    session: Client,
-    request_timeout_in_sec: Duration,
-}
-
-impl OutlineVPN<'_> {
-    fn call_api(
-        &self,
-        api_path: &str,
-        request_method: reqwest::Method,
-        request_body: String,
-    ) -> Result<Response, reqwest::Error> {
-        let url = format!("{}{}", self.api_url, api_path);
-        debug!("URL: {}", url);
-        debug!("Method: {:?}", request_method);
-        debug!("Request Body: {}", request_body);
-        let response = self
-            .session
-            .request(request_method, &url)
-            .timeout(self.request_timeout_in_sec)
-            .header(reqwest::header::CONTENT_TYPE, "application/json")
-            .body(request_body)
-            .send()?;
-
-        Ok(response)
-    }
-
-    /// Get server information.
-    ///
-    /// Responses:
-    ///
-    /// - `200` – Server information.
-    pub fn get_server_info(&self) -> Result<serde_json::Value, String> {
-        let response = match self.call_api(SERVER_ENDPOINT, reqwest::Method::GET, String::new()) {
-            Ok(response) => response,
-            Err(_) => return Err(APIError::UnknownServerError.to_string()),
-        };
-
-        handle_json_api_result(response)
-    }
-
-    /// Change hostname for access keys.
-    ///
-    /// Responses:
-    ///
-    ///  - `204` – The hostname was successfully changed.
-    ///  - `400` – An invalid hostname or IP address was provided.
-    ///  - `500` – An internal error occurred.  This could be thrown if there were network errors while validating the hostname.
-    pub fn change_hostname_for_access_keys(&self, hostname: &str) -> Result<(), String> {
-        let body = format!(r#"{{ "hostname": "{}" }}"#, hostname);
-        let response = match self.call_api(HOSTNAME_ENDPOINT, reqwest::Method::PUT, body) {
-            Ok(response) => response,
-            Err(_) => return Err(APIError::UnknownServerError.to_string()),
-        };
-
-        handle_response_status(&response, HOSTNAME_ENDPOINT)
-    }
-
-    /// Change default port for newly created access keys.
-    ///
-    /// Responses:
-    ///
-    /// - `204` – The default port was successfully changed.
-    /// - `400` – The requested port wasn't an integer from 1 through 65535, or the request had no port parameter.
-    /// - `409` – The requested port was already in use by another service.
-    pub fn change_default_port_for_newly_created_access(&self, port: &str) -> Result<(), String> {
-        let body = format!(r#"{{ "port": {} }}"#, port);
-        let response = match self.call_api(CHANGE_PORT_ENDPOINT, reqwest::Method::PUT, body) {
-            Ok(response) => response,
-            Err(_) => return Err(APIError::UnknownServerError.to_string()),
-        };
-
-        handle_response_status(&response, CHANGE_PORT_ENDPOINT)
-    }
-
-    /// Set data transfer limit (in bytes) for all access keys.
-    ///
-    /// Responses:
-    ///
-    /// - `204` – Access key data limit set successfully.
-    /// - `400` – Invalid data limit.
-    pub fn set_data_transfer_limit_for_all_access_keys(&self, byte: &u64) -> Result<(), String> {
-        let body = format!(r#"{{ "limit": {{ "bytes": {} }} }}"#, byte);
-        let response = match self.call_api(KEY_DATA_LIMIT_ENDPOINT, reqwest::Method::PUT, body) {
-            Ok(response) => response,
-            Err(_) => return Err(APIError::UnknownServerError.to_string()),
-        };
-
-        handle_response_status(&response, KEY_DATA_LIMIT_ENDPOINT)
-    }
-
-    /// Remove data transfer limit for all access keys.
-    ///
-    /// Responses:
-    ///
-    /// - `204` – Access key limit deleted successfully.
-    pub fn remove_data_limit_for_all_access_keys(&self) -> Result<(), String> {
-        let response = match self.call_api(
-            KEY_DATA_LIMIT_ENDPOINT,
-            reqwest::Method::DELETE,
-            String::new(),
-        ) {
-            Ok(response) => response,
-            Err(_) => return Err(APIError::UnknownServerError.to_string()),
-        };
-
-        handle_response_status(&response, KEY_DATA_LIMIT_ENDPOINT)
-    }
-
-    /// Rename server.
-    ///
-    /// Responses:
-    ///
-    /// - `204` – Server renamed successfully.
-    /// - `400` – Invalid name.
-    pub fn rename_server(&self, name: &str) -> Result<(), String> {
-        let body = format!(r#"{{ "name": "{}" }}"#, name);
-        let response = match self.call_api(NAME_ENDPOINT, reqwest::Method::PUT, body) {
-            Ok(response) => response,
-            Err(_) => return Err(APIError::UnknownServerError.to_string()),
-        };
-
-        handle_response_status(&response, NAME_ENDPOINT)
-    }
-
-    /// Create new access key.
-    ///
-    /// Responses:
-    ///
-    /// - `201` – The newly created access key.
-    pub fn create_access_key(&self) -> Result<serde_json::Value, String> {
-        let response =
-            match self.call_api(ACCESS_KEYS_ENDPOINT, reqwest::Method::POST, String::new()) {
-                Ok(response) => response,
-                Err(_) => return Err(APIError::UnknownServerError.to_string()),
-            };
-
-        handle_json_api_result(response)
-    }
-
-    /// Display complete list of the access keys.
-    ///
-    /// Responses:
-    ///
-    /// - `200` – List of access keys.
-    pub fn list_access_keys(&self) -> Result<serde_json::Value, String> {
-        let response =
-            match self.call_api(ACCESS_KEYS_ENDPOINT, reqwest::Method::GET, String::new()) {
-                Ok(response) => response,
-                Err(_) => return Err(APIError::UnknownServerError.to_string()),
-            };
-
-        handle_json_api_result(response)
-    }
-
-    // /// Incorrect API specification, this method is defined in the API, but is not actually supported by the server!!!
-    // /// Get access key by ID.
-    // ///
-    // /// Responses:
-    // ///
-    // /// - 200 – The access key.
-    // /// - 404 – Access key inexistent.
-    // pub fn get_access_key_by_id(&self, id: &u16) -> Result<serde_json::Value, String> {
-    //     let api_path = format!("{}/{}", ACCESS_KEYS_ENDPOINT, id);
-    //     let response = match self.call_api(&api_path, reqwest::Method::GET, String::new()) {
-    //         Ok(response) => response,
-    //         Err(_) => return Err(APIError::UnknownServerError.to_string()),
-    //     };
-    //
-    //     handle_api_error(response)
-    // }
-
-    /// Delete access key by ID.
-    ///
-    /// Responses:
-    ///
-    /// - `204` – Access key deleted successfully.
-    /// - `404` – Access key inexistent.
-    pub fn delete_access_key_by_id(&self, id: &u16) -> Result<(), String> {
-        let api_path = format!("{}/{}", ACCESS_KEYS_ENDPOINT, id);
-        let response = match self.call_api(&api_path, reqwest::Method::DELETE, String::new()) {
-            Ok(response) => response,
-            Err(_) => return Err(APIError::UnknownServerError.to_string()),
-        };
-
-        handle_response_status(&response, ACCESS_KEYS_ENDPOINT)
-    }
-
-    /// Change name for access key (by ID).
-    ///
-    /// Responses:
-    ///
-    /// - `204` – Access key renamed successfully.
-    /// - `404` – Access key inexistent.
-    pub fn change_name_for_access_key(&self, id: &u16, username: &str) -> Result<(), String> {
-        let body = format!(r#"{{ "name": "{}" }}"#, username);
-        let api_path = format!("{}/{}/name", ACCESS_KEYS_ENDPOINT, id);
-        let response = match self.call_api(&api_path, reqwest::Method::PUT, body) {
-            Ok(response) => response,
-            Err(_) => return Err(APIError::UnknownServerError.to_string()),
-        };
-
-        handle_response_status(&response, ACCESS_KEYS_ENDPOINT)
-    }
-
-    /// Set data transfer limit by ID.
-    ///
-    /// Responses:
-    ///
-    /// - `204` – Access key limit set successfully.
-    /// - `400` – Invalid data limit.
-    /// - `404` –  Access key inexistent.
-    pub fn set_data_transfer_limit_by_id(&self, id: &u16, byte: &u64) -> Result<(), String> {
-        let body = format!(r#"{{ "limit": {{ "bytes": {} }} }}"#, byte);
-        let api_path = format!("{}/{}/data-limit", ACCESS_KEYS_ENDPOINT, id);
-        let response = match self.call_api(&api_path, reqwest::Method::PUT, body) {
-            Ok(response) => response,
-            Err(_) => return Err(APIError::UnknownServerError.to_string()),
-        };
-
-        handle_response_status(&response, ACCESS_KEYS_ENDPOINT)
-    }
-
-    /// Remove data transfer limit by ID.
-    ///
-    /// Responses:
-    ///
-    /// - `204` – Access key limit deleted successfully.
-    /// - `404` – Access key inexistent.
-    pub fn del_data_transfer_limit_by_id(&self, id: &u16) -> Result<(), String> {
-        let api_path = format!("{}/{}/data-limit", ACCESS_KEYS_ENDPOINT, id);
-        let response = match self.call_api(&api_path, reqwest::Method::DELETE, String::new()) {
-            Ok(response) => response,
-            Err(_) => return Err(APIError::UnknownServerError.to_string()),
-        };
-
-        handle_response_status(&response, ACCESS_KEYS_ENDPOINT)
-    }
-
-    /// Get data transfer stats for each access key in bytes.
-    ///
-    /// Responses:
-    ///
-    /// - `200` – The data transferred by each access key.
-    pub fn get_each_access_key_data_transferred(&self) -> Result<serde_json::Value, String> {
-        let api_path = format!("{}/transfer", METRICS_ENDPOINT);
-        let response = match self.call_api(&api_path, reqwest::Method::GET, String::new()) {
-            Ok(response) => response,
-            Err(_) => return Err(APIError::UnknownServerError.to_string()),
-        };
-
-        handle_json_api_result(response)
-    }
-
-    /// Get 'Share anonymous metrics' status.
-    ///
-    /// Responses:
-    ///
-    /// - `200` – The metrics enabled setting.
-    pub fn get_whether_metrics_is_being_shared(&self) -> Result<serde_json::Value, String> {
-        let api_path = format!("{}/enabled", METRICS_ENDPOINT);
-        let response = match self.call_api(&api_path, reqwest::Method::GET, String::new()) {
-            Ok(response) => response,
-            Err(_) => return Err(APIError::UnknownServerError.to_string()),
-        };
-
-        handle_json_api_result(response)
-    }
-
-    /// Enable or disable 'Share anonymous metrics' setting.
-    ///
-    /// Responses:
-    ///
-    /// - `204` – Setting successful.
-    /// - `400` – Invalid request.
-    pub fn enable_or_disable_sharing_metrics(&self, metrics_enabled: bool) -> Result<(), String> {
-        let body = format!(r#"{{ "metricsEnabled": {} }}"#, metrics_enabled);
-        let api_path = format!("{}/enabled", METRICS_ENDPOINT);
-        let response = match self.call_api(&api_path, reqwest::Method::PUT, body) {
-            Ok(response) => response,
-            Err(_) => return Err(APIError::UnknownServerError.to_string()),
-        };
-
-        handle_response_status(&response, METRICS_ENDPOINT)
+pub(crate) fn handle_response_status(
+    response: RawResponse,
+    api_path: &str,
+) -> Result<(), APIError> {
+    match response.status {
+        StatusCode::OK => Ok(()),
+        StatusCode::NO_CONTENT => Ok(()),
+        _ => Err(api_error(response, api_path)),
     }
 }
 
-/// Creates a new `OutlineVPN` client to interact with the Outline VPN Server management API.
-///
-/// This function initializes and configures an `OutlineVPN` client with the provided parameters.
-///
-/// # Arguments
-///
-/// - `cert_sha256`: A reference to a string representing the SHA-256 hash of the server's certificate.
-/// - `api_url`: A reference to a string representing the URL of the Outline VPN server API.
-/// - `request_timeout_in_sec`: `Duration` specifying the timeout for API requests.
-///
-/// # Returns
-///
-/// Returns an `OutlineVPN` client configured with the specified parameters.
-///
-/// # Examples:
-///
-/// ## Creating an OutlineVPM API client:
-///
-/// ```rust
-/// use std::time::Duration;
-///
-/// // Reading from the `config.rs` is preferred way, see README.md at github repo
-/// // https://github.com/sigseg5/outline-api/blob/master/README.md
-/// let api_url = "https://example.com/secret";
-/// let cert_sha256 = "cert_sha256_hash";
-/// let request_timeout = Duration::from_secs(10);
-///
-/// let outline_vpn = outline_api::new(api_url, cert_sha256, request_timeout);
-///
-/// // Performing operations using the Client:
-///
-/// match outline_vpn.get_server_info() {
-///     Ok(server_info) => {
-///         println!("Server information: {}", server_info);
-///     },
-///     Err(err) => {
-///         eprintln!("Error: {}", err);
-///     }
-/// }
-/// ```
-pub fn new<'a>(
-    cert_sha256: &'a str,
-    api_url: &'a str,
-    request_timeout: Duration,
-) -> OutlineVPN<'a> {
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        "Certificate-SHA256",
-        reqwest::header::HeaderValue::from_str(cert_sha256).unwrap(),
-    );
-
-    // .danger_accept_invalid_certs(true) is safe to use because it uses a self-issued encryption certificate when the server is created
-    let session = Client::builder()
-        .danger_accept_invalid_certs(true)
-        .default_headers(headers)
-        .build()
-        .unwrap();
-
-    OutlineVPN {
-        api_url,
-        session,
-        request_timeout_in_sec: request_timeout,
+/// Maps a non-successful API response to the matching `APIError` variant.
+///
+/// The response body is kept in the returned error together with the status code
+/// and `api_path`. `BAD_REQUEST` is mapped to specific API errors based on the `api_path`, and
+/// `CONFLICT` on `/access-keys/{id}` means that the requested key ID is already taken.
+fn api_error(response: RawResponse, api_path: &str) -> APIError {
+    let details = ErrorResponse {
+        status: response.status,
+        endpoint: api_path.to_string(),
+        body: response.body,
+    };
+    match details.status {
+        StatusCode::BAD_REQUEST => match api_path {
+            NAME_ENDPOINT => APIError::InvalidName(details),
+            HOSTNAME_ENDPOINT => APIError::InvalidHostname(details),
+            CHANGE_PORT_ENDPOINT => APIError::InvalidPort(details),
+            KEY_DATA_LIMIT_ENDPOINT => APIError::InvalidDataLimit(details),
+            path if path.starts_with(ACCESS_KEYS_ENDPOINT) && path.ends_with("/data-limit") => {
+                APIError::InvalidDataLimit(details)
+            }
+            path if path.starts_with(ACCESS_KEYS_ENDPOINT) && path.ends_with("/name") => {
+                APIError::InvalidName(details)
+            }
+            _ => APIError::InvalidRequest(details),
+        },
+        StatusCode::CONFLICT if is_access_key_path(api_path) => {
+            APIError::AccessKeyAlreadyExists(details)
+        }
+        StatusCode::CONFLICT => APIError::PortConflict(details),
+        StatusCode::NOT_FOUND => APIError::AccessKeyInexistent(details),
+        StatusCode::INTERNAL_SERVER_ERROR => APIError::InternalError(details),
+        _ => APIError::UnknownError(details),
     }
 }
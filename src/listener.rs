@@ -0,0 +1,130 @@
+//! Minimal HTTP/1.1 server plumbing shared by the exporter and the mock server.
+
+use std::io::{self, Read};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Accepts connections on a background thread until dropped, handing each one to its own thread.
+#[derive(Debug)]
+pub(crate) struct Listener {
+    address: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    accept: Option<JoinHandle<()>>,
+}
+
+impl Listener {
+    /// Starts accepting connections on `listener`, serving each one with `handler`.
+    pub(crate) fn spawn<F>(listener: TcpListener, handler: F) -> io::Result<Listener>
+    where
+        F: Fn(TcpStream) -> io::Result<()> + Send + Sync + 'static,
+    {
+        let address = listener.local_addr()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handler = Arc::new(handler);
+
+        let accept_shutdown = Arc::clone(&shutdown);
+        let accept = thread::spawn(move || {
+            for socket in listener.incoming() {
+                if accept_shutdown.load(Ordering::SeqCst) {
+                    return;
+                }
+                let Ok(socket) = socket else { continue };
+                let handler = Arc::clone(&handler);
+                thread::spawn(move || {
+                    // Errors only affect this connection, and the client reports them
+                    let _ = handler(socket);
+                });
+            }
+        });
+
+        Ok(Listener {
+            address,
+            shutdown,
+            accept: Some(accept),
+        })
+    }
+
+    /// Returns the address connections are accepted on.
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Blocks until the accept loop stops, which only happens once the listener is dropped.
+    pub(crate) fn join(&mut self) {
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wakes up the accept loop so it notices the shutdown
+        let mut address = self.address;
+        if address.ip().is_unspecified() {
+            address.set_ip(match address {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        let _ = TcpStream::connect_timeout(&address, Duration::from_secs(1));
+    }
+}
+
+/// A request read by `read_request`.
+pub(crate) struct Request {
+    pub(crate) method: String,
+    /// Request target, e.g. `/metrics?name=value`. Not percent-decoded.
+    pub(crate) target: String,
+    /// Only read by the mock server, as scrapers send no body.
+    #[cfg_attr(not(feature = "test-util"), allow(dead_code))]
+    pub(crate) body: String,
+}
+
+/// Reads a request head and its `Content-Length` body.
+///
+/// Returns `None` if the connection closes early or the request is larger than `max_size`.
+pub(crate) fn read_request(stream: &mut impl Read, max_size: usize) -> io::Result<Option<Request>> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let header_end = loop {
+        if let Some(position) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+        if request.len() > max_size {
+            return Ok(None);
+        }
+        match stream.read(&mut buffer)? {
+            0 => return Ok(None),
+            read => request.extend_from_slice(&buffer[..read]),
+        }
+    };
+    let head = String::from_utf8_lossy(&request[..header_end]).into_owned();
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    if header_end + content_length > max_size {
+        return Ok(None);
+    }
+    while request.len() < header_end + content_length {
+        match stream.read(&mut buffer)? {
+            0 => return Ok(None),
+            read => request.extend_from_slice(&buffer[..read]),
+        }
+    }
+
+    let mut request_line = head.split_whitespace();
+    Ok(Some(Request {
+        method: request_line.next().unwrap_or_default().to_string(),
+        target: request_line.next().unwrap_or_default().to_string(),
+        body: String::from_utf8_lossy(&request[header_end..header_end + content_length])
+            .into_owned(),
+    }))
+}
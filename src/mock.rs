@@ -0,0 +1,792 @@
+//! In-process mock of the Outline VPN management API, for end-to-end tests.
+//!
+//! Available with the `test-util` feature. `MockServer` listens on `127.0.0.1` with a freshly
+//! generated self-signed certificate and serves every path of `api.yml` under a random secret
+//! prefix, keeping the server settings and access keys in memory. Status codes follow the real
+//! server: `204` for successful updates, `400` for invalid input, `404` for unknown keys and
+//! `409` for conflicts.
+//!
+//! ```rust
+//! # #[cfg(feature = "blocking")]
+//! # fn main() -> Result<(), outline_api::APIError> {
+//! use outline_api::mock::MockServer;
+//!
+//! let server = MockServer::start();
+//! let vpn = server.client();
+//!
+//! let key = vpn.create_access_key()?;
+//! vpn.change_name_for_access_key(&key.id, "Alice")?;
+//!
+//! assert_eq!(server.access_keys()[0].name, "Alice");
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "blocking"))]
+//! # fn main() {}
+//! ```
+
+use crate::listener::{read_request, Listener, Request};
+use crate::{random_u64, AccessKey, AccessKeyId, DataLimit, ExtraFields, Server};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use percent_encoding::percent_decode_str;
+use rustls::{Certificate, PrivateKey, ServerConfig, ServerConnection, StreamOwned};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Encryption methods accepted when creating an access key.
+const SUPPORTED_METHODS: &[&str] = &[
+    "chacha20-ietf-poly1305",
+    "aes-128-gcm",
+    "aes-192-gcm",
+    "aes-256-gcm",
+];
+
+const DEFAULT_METHOD: &str = "chacha20-ietf-poly1305";
+
+/// Largest accepted request, headers included.
+const MAX_REQUEST_SIZE: usize = 1 << 20;
+
+/// A request received by the mock server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockRequest {
+    /// HTTP method, e.g. `PUT`.
+    pub method: String,
+    /// Path without the secret prefix, e.g. `/access-keys/1/name`. Not percent-decoded.
+    pub path: String,
+    /// Raw request body.
+    pub body: String,
+}
+
+/// Builder for a `MockServer`, created with `MockServer::builder()`.
+#[derive(Debug, Clone)]
+pub struct MockServerBuilder {
+    name: String,
+    hostname: String,
+    port_for_new_access_keys: u16,
+    legacy: bool,
+}
+
+impl MockServerBuilder {
+    /// Sets the initial server name. Defaults to `Outline Server`.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Sets the initial hostname used in access URLs. Defaults to `127.0.0.1`.
+    pub fn hostname(mut self, hostname: impl Into<String>) -> Self {
+        self.hostname = hostname.into();
+        self
+    }
+
+    /// Sets the initial port for new access keys. Defaults to `12345`.
+    pub fn port_for_new_access_keys(mut self, port: u16) -> Self {
+        self.port_for_new_access_keys = port;
+        self
+    }
+
+    /// Behaves like older servers, which implement neither `GET /access-keys/{id}` nor
+    /// `PUT /access-keys/{id}` and answer these requests with `405`, and ignore the `name` and
+    /// `limit` of keys created with `POST /access-keys`.
+    pub fn legacy(mut self, legacy: bool) -> Self {
+        self.legacy = legacy;
+        self
+    }
+
+    /// Starts the server on a random local port.
+    ///
+    /// # Panics
+    ///
+    /// If the certificate can't be generated or the listener can't be bound.
+    pub fn start(self) -> MockServer {
+        let certificate = rcgen::generate_simple_self_signed(vec![
+            "127.0.0.1".to_string(),
+            "localhost".to_string(),
+        ])
+        .expect("mock server certificate should be generated");
+        let der = certificate
+            .serialize_der()
+            .expect("mock server certificate should serialize");
+        let cert_sha256 = Sha256::digest(&der)
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<String>();
+        let tls_config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![Certificate(der)],
+                PrivateKey(certificate.serialize_private_key_der()),
+            )
+            .expect("mock server certificate should be usable");
+
+        let listener =
+            TcpListener::bind("127.0.0.1:0").expect("mock server should bind to a local port");
+        let secret = random_token(22);
+
+        let state = Arc::new(Mutex::new(State {
+            server: Server {
+                name: self.name,
+                server_id: random_server_id(),
+                metrics_enabled: false,
+                created_timestamp_ms: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_millis() as u64)
+                    .unwrap_or_default(),
+                version: "1.8.0".to_string(),
+                access_key_data_limit: None,
+                port_for_new_access_keys: self.port_for_new_access_keys,
+                hostname_for_access_keys: self.hostname,
+                extra: ExtraFields::new(),
+            },
+            access_keys: Vec::new(),
+            bytes_transferred: HashMap::new(),
+            occupied_ports: BTreeSet::new(),
+            failures: VecDeque::new(),
+            requests: Vec::new(),
+            legacy: self.legacy,
+        }));
+        let accept_state = Arc::clone(&state);
+        let prefix = format!("/{}", secret);
+        let tls_config = Arc::new(tls_config);
+        let listener = Listener::spawn(listener, move |socket| {
+            serve_connection(socket, Arc::clone(&tls_config), &prefix, &accept_state)
+        })
+        .expect("mock server should have a local address");
+
+        MockServer {
+            api_url: format!("https://{}/{}", listener.local_addr(), secret),
+            cert_sha256,
+            listener,
+            state,
+        }
+    }
+}
+
+/// Mock Outline server running on a background thread until dropped.
+///
+/// See the module documentation for an example.
+pub struct MockServer {
+    api_url: String,
+    cert_sha256: String,
+    listener: Listener,
+    state: Arc<Mutex<State>>,
+}
+
+impl std::fmt::Debug for MockServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("MockServer")
+            .field("address", &self.listener.local_addr())
+            .finish_non_exhaustive()
+    }
+}
+
+impl MockServer {
+    /// Starts a server with the default settings.
+    ///
+    /// # Panics
+    ///
+    /// If the certificate can't be generated or the listener can't be bound.
+    pub fn start() -> MockServer {
+        MockServer::builder().start()
+    }
+
+    /// Returns a builder to configure the server before starting it.
+    pub fn builder() -> MockServerBuilder {
+        MockServerBuilder {
+            name: "Outline Server".to_string(),
+            hostname: "127.0.0.1".to_string(),
+            port_for_new_access_keys: 12345,
+            legacy: false,
+        }
+    }
+
+    /// Returns the API URL, including the secret prefix.
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    /// Returns the SHA-256 fingerprint of the server certificate, as uppercase hex.
+    pub fn cert_sha256(&self) -> &str {
+        &self.cert_sha256
+    }
+
+    /// Returns the access config JSON, as printed by the Outline installer.
+    pub fn access_config(&self) -> String {
+        json!({ "apiUrl": self.api_url, "certSha256": self.cert_sha256 }).to_string()
+    }
+
+    /// Returns a blocking client for this server.
+    #[cfg(feature = "blocking")]
+    pub fn client(&self) -> crate::blocking::OutlineVPN {
+        crate::blocking::OutlineVPN::builder()
+            .api_url(&self.api_url)
+            .cert_sha256(&self.cert_sha256)
+            .timeout(Duration::from_secs(10))
+            .build()
+            .expect("mock server settings should be valid")
+    }
+
+    /// Returns an async client for this server.
+    #[cfg(feature = "async")]
+    pub fn async_client(&self) -> crate::asynchronous::OutlineVPN {
+        crate::asynchronous::OutlineVPN::builder()
+            .api_url(&self.api_url)
+            .cert_sha256(&self.cert_sha256)
+            .timeout(Duration::from_secs(10))
+            .build()
+            .expect("mock server settings should be valid")
+    }
+
+    /// Returns the current server information.
+    pub fn server(&self) -> Server {
+        self.state().server.clone()
+    }
+
+    /// Returns the current access keys, in creation order.
+    pub fn access_keys(&self) -> Vec<AccessKey> {
+        let state = self.state();
+        state
+            .access_keys
+            .iter()
+            .map(|key| state.render_key(key))
+            .collect()
+    }
+
+    /// Sets the bytes reported for a key by `GET /metrics/transfer`.
+    pub fn set_bytes_transferred(&self, id: impl Into<AccessKeyId>, bytes: u64) {
+        self.state().bytes_transferred.insert(id.into(), bytes);
+    }
+
+    /// Marks a port as used by another service, so requests to use it fail with `409`.
+    pub fn occupy_port(&self, port: u16) {
+        self.state().occupied_ports.insert(port);
+    }
+
+    /// Answers the next `count` requests with `status` and an error body, without handling them.
+    pub fn fail_next_requests(&self, count: usize, status: u16) {
+        let mut state = self.state();
+        state.failures.extend(std::iter::repeat(status).take(count));
+    }
+
+    /// Returns every request received so far, oldest first.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state().requests.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // A panic while holding the lock can't leave the state half-updated in a way tests care about
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// An access key as stored by the server: the access URL is derived when it's rendered.
+#[derive(Debug, Clone)]
+struct StoredKey {
+    id: AccessKeyId,
+    name: String,
+    password: String,
+    port: u16,
+    method: String,
+    data_limit: Option<DataLimit>,
+}
+
+struct State {
+    server: Server,
+    access_keys: Vec<StoredKey>,
+    bytes_transferred: HashMap<AccessKeyId, u64>,
+    occupied_ports: BTreeSet<u16>,
+    failures: VecDeque<u16>,
+    requests: Vec<MockRequest>,
+    legacy: bool,
+}
+
+struct Response {
+    status: u16,
+    body: Option<Value>,
+}
+
+impl Response {
+    fn no_content() -> Self {
+        Response {
+            status: 204,
+            body: None,
+        }
+    }
+
+    fn json(status: u16, body: Value) -> Self {
+        Response {
+            status,
+            body: Some(body),
+        }
+    }
+
+    fn error(status: u16, code: &str, message: impl Into<String>) -> Self {
+        Response::json(status, json!({ "code": code, "message": message.into() }))
+    }
+
+    fn key_not_found(id: &AccessKeyId) -> Self {
+        Response::error(
+            404,
+            "NotFound",
+            format!("No access key found with id {}", id),
+        )
+    }
+}
+
+impl State {
+    fn render_key(&self, key: &StoredKey) -> AccessKey {
+        let user_info = STANDARD.encode(format!("{}:{}", key.method, key.password));
+        AccessKey {
+            id: key.id.clone(),
+            name: key.name.clone(),
+            password: key.password.clone(),
+            port: key.port,
+            method: key.method.clone(),
+            data_limit: key.data_limit,
+            access_url: format!(
+                "ss://{}@{}:{}/?outline=1",
+                user_info, self.server.hostname_for_access_keys, key.port
+            ),
+            extra: ExtraFields::new(),
+        }
+    }
+
+    fn find_key(&mut self, id: &AccessKeyId) -> Option<&mut StoredKey> {
+        self.access_keys.iter_mut().find(|key| key.id == *id)
+    }
+
+    /// Returns `true` if `port` is taken by another service. Ports of access keys can be shared.
+    fn is_port_conflict(&self, port: u16) -> bool {
+        self.occupied_ports.contains(&port)
+    }
+
+    fn handle(&mut self, method: &str, path: &str, body: &str) -> Response {
+        if let Some(status) = self.failures.pop_front() {
+            return Response::error(status, "MockFailure", "Injected failure");
+        }
+
+        let body = if body.trim().is_empty() {
+            Value::Null
+        } else {
+            match serde_json::from_str(body) {
+                Ok(body) => body,
+                Err(err) => return Response::error(400, "BadRequest", err.to_string()),
+            }
+        };
+        let segments: Vec<String> = path
+            .trim_start_matches('/')
+            .split('/')
+            .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned())
+            .collect();
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+        match (method, segments.as_slice()) {
+            ("GET", ["server"]) => Response::json(200, to_value(&self.server)),
+            ("PUT", ["server", "hostname-for-access-keys"]) => self.set_hostname(&body),
+            ("PUT", ["server", "port-for-new-access-keys"]) => self.set_default_port(&body),
+            ("PUT", ["server", "access-key-data-limit"])
+            | ("PUT", ["experimental", "access-key-data-limit"]) => match parse_limit(&body) {
+                Ok(limit) => {
+                    self.server.access_key_data_limit = Some(limit);
+                    Response::no_content()
+                }
+                Err(response) => response,
+            },
+            ("DELETE", ["server", "access-key-data-limit"])
+            | ("DELETE", ["experimental", "access-key-data-limit"]) => {
+                self.server.access_key_data_limit = None;
+                Response::no_content()
+            }
+            ("PUT", ["name"]) => match parse_name(&body) {
+                Ok(name) => {
+                    self.server.name = name;
+                    Response::no_content()
+                }
+                Err(response) => response,
+            },
+            ("POST", ["access-keys"]) => self.create_key(None, &body),
+            ("GET", ["access-keys"]) => {
+                let access_keys: Vec<AccessKey> = self
+                    .access_keys
+                    .iter()
+                    .map(|key| self.render_key(key))
+                    .collect();
+                Response::json(200, json!({ "accessKeys": to_value(&access_keys) }))
+            }
+            ("GET", ["access-keys", id]) if !self.legacy => {
+                let id = AccessKeyId::from(*id);
+                match self.access_keys.iter().find(|key| key.id == id) {
+                    Some(key) => Response::json(200, to_value(&self.render_key(key))),
+                    None => Response::key_not_found(&id),
+                }
+            }
+            ("PUT", ["access-keys", id]) if !self.legacy => {
+                self.create_key(Some(AccessKeyId::from(*id)), &body)
+            }
+            ("DELETE", ["access-keys", id]) => {
+                let id = AccessKeyId::from(*id);
+                match self.access_keys.iter().position(|key| key.id == id) {
+                    Some(index) => {
+                        self.access_keys.remove(index);
+                        self.bytes_transferred.remove(&id);
+                        Response::no_content()
+                    }
+                    None => Response::key_not_found(&id),
+                }
+            }
+            ("PUT", ["access-keys", id, "name"]) => {
+                let id = AccessKeyId::from(*id);
+                let name = match parse_name(&body) {
+                    Ok(name) => name,
+                    Err(response) => return response,
+                };
+                match self.find_key(&id) {
+                    Some(key) => {
+                        key.name = name;
+                        Response::no_content()
+                    }
+                    None => Response::key_not_found(&id),
+                }
+            }
+            ("PUT", ["access-keys", id, "data-limit"]) => {
+                let id = AccessKeyId::from(*id);
+                let limit = match parse_limit(&body) {
+                    Ok(limit) => limit,
+                    Err(response) => return response,
+                };
+                match self.find_key(&id) {
+                    Some(key) => {
+                        key.data_limit = Some(limit);
+                        Response::no_content()
+                    }
+                    None => Response::key_not_found(&id),
+                }
+            }
+            ("DELETE", ["access-keys", id, "data-limit"]) => {
+                let id = AccessKeyId::from(*id);
+                match self.find_key(&id) {
+                    Some(key) => {
+                        key.data_limit = None;
+                        Response::no_content()
+                    }
+                    None => Response::key_not_found(&id),
+                }
+            }
+            ("GET", ["metrics", "transfer"]) => Response::json(
+                200,
+                json!({ "bytesTransferredByUserId": to_value(&self.bytes_transferred) }),
+            ),
+            ("GET", ["metrics", "enabled"]) => Response::json(
+                200,
+                json!({ "metricsEnabled": self.server.metrics_enabled }),
+            ),
+            ("PUT", ["metrics", "enabled"]) => match body.get("metricsEnabled") {
+                Some(Value::Bool(enabled)) => {
+                    self.server.metrics_enabled = *enabled;
+                    Response::no_content()
+                }
+                _ => Response::error(
+                    400,
+                    "InvalidArgument",
+                    "Parameter `metricsEnabled` must be a boolean",
+                ),
+            },
+            (_, segments) if self.is_known_path(segments) => Response::error(
+                405,
+                "MethodNotAllowed",
+                format!("{} is not allowed", method),
+            ),
+            _ => Response::error(404, "ResourceNotFound", format!("{} does not exist", path)),
+        }
+    }
+
+    fn is_known_path(&self, segments: &[&str]) -> bool {
+        matches!(
+            segments,
+            ["server"]
+                | ["server", "hostname-for-access-keys"]
+                | ["server", "port-for-new-access-keys"]
+                | ["server", "access-key-data-limit"]
+                | ["experimental", "access-key-data-limit"]
+                | ["name"]
+                | ["access-keys"]
+                | ["access-keys", _]
+                | ["access-keys", _, "name"]
+                | ["access-keys", _, "data-limit"]
+                | ["metrics", "transfer"]
+                | ["metrics", "enabled"]
+        )
+    }
+
+    fn set_hostname(&mut self, body: &Value) -> Response {
+        match body.get("hostname").and_then(Value::as_str) {
+            Some(hostname) if is_valid_hostname(hostname) => {
+                self.server.hostname_for_access_keys = hostname.to_string();
+                Response::no_content()
+            }
+            _ => Response::error(
+                400,
+                "InvalidArgument",
+                "Parameter `hostname` must be a valid hostname or IP address",
+            ),
+        }
+    }
+
+    fn set_default_port(&mut self, body: &Value) -> Response {
+        let port = match parse_port(body.get("port")) {
+            Some(port) => port,
+            None => {
+                return Response::error(
+                    400,
+                    "InvalidArgument",
+                    "Parameter `port` must be an integer from 1 through 65535",
+                )
+            }
+        };
+        if self.is_port_conflict(port) {
+            return Response::error(409, "Conflict", format!("Port {} is already in use", port));
+        }
+        self.server.port_for_new_access_keys = port;
+        Response::no_content()
+    }
+
+    fn create_key(&mut self, id: Option<AccessKeyId>, body: &Value) -> Response {
+        if !body.is_null() && !body.is_object() {
+            return Response::error(400, "InvalidArgument", "Expected a JSON object");
+        }
+
+        let method = match body.get("method") {
+            None | Some(Value::Null) => DEFAULT_METHOD.to_string(),
+            Some(Value::String(method)) if SUPPORTED_METHODS.contains(&method.as_str()) => {
+                method.clone()
+            }
+            Some(_) => {
+                return Response::error(400, "InvalidArgument", "Unsupported encryption method")
+            }
+        };
+        let name = match body.get("name") {
+            None | Some(Value::Null) => None,
+            Some(_) => match parse_name(body) {
+                Ok(name) => Some(name),
+                Err(response) => return response,
+            },
+        };
+        let port = match body.get("port") {
+            None | Some(Value::Null) => self.server.port_for_new_access_keys,
+            Some(port) => match parse_port(Some(port)) {
+                Some(port) => port,
+                None => {
+                    return Response::error(
+                        400,
+                        "InvalidArgument",
+                        "Parameter `port` must be an integer from 1 through 65535",
+                    )
+                }
+            },
+        };
+        if self.is_port_conflict(port) {
+            return Response::error(409, "Conflict", format!("Port {} is already in use", port));
+        }
+        let password = match body.get("password") {
+            None | Some(Value::Null) => random_token(22),
+            Some(Value::String(password)) => password.clone(),
+            Some(_) => {
+                return Response::error(
+                    400,
+                    "InvalidArgument",
+                    "Parameter `password` must be a string",
+                )
+            }
+        };
+        let data_limit = match body.get("limit") {
+            None | Some(Value::Null) => None,
+            Some(_) => match parse_limit(body) {
+                Ok(limit) => Some(limit),
+                Err(response) => return response,
+            },
+        };
+
+        let id = match id {
+            Some(id) => {
+                if self.access_keys.iter().any(|key| key.id == id) {
+                    return Response::error(
+                        409,
+                        "Conflict",
+                        format!("Access key \"{}\" already exists", id),
+                    );
+                }
+                id
+            }
+            None => self.next_id(),
+        };
+        let (name, data_limit) = if self.legacy {
+            (None, None)
+        } else {
+            (name, data_limit)
+        };
+        let key = StoredKey {
+            id,
+            name: name.unwrap_or_default(),
+            password,
+            port,
+            method,
+            data_limit,
+        };
+        let response = Response::json(201, to_value(&self.render_key(&key)));
+        self.access_keys.push(key);
+        response
+    }
+
+    /// Picks the smallest unused numeric ID, like the real server does for keys without a custom ID.
+    fn next_id(&self) -> AccessKeyId {
+        (0u64..)
+            .map(AccessKeyId::from)
+            .find(|id| self.access_keys.iter().all(|key| key.id != *id))
+            .expect("there should be a free numeric ID")
+    }
+}
+
+fn to_value<T: serde::Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).expect("mock responses should serialize to JSON")
+}
+
+fn parse_name(body: &Value) -> Result<String, Response> {
+    match body.get("name") {
+        Some(Value::String(name)) if name.chars().count() <= 100 => Ok(name.clone()),
+        _ => Err(Response::error(
+            400,
+            "InvalidArgument",
+            "Parameter `name` must be a string of at most 100 characters",
+        )),
+    }
+}
+
+fn parse_limit(body: &Value) -> Result<DataLimit, Response> {
+    body.get("limit")
+        .and_then(|limit| limit.get("bytes"))
+        .and_then(Value::as_u64)
+        .map(DataLimit::new)
+        .ok_or_else(|| {
+            Response::error(
+                400,
+                "InvalidArgument",
+                "Parameter `limit.bytes` must be a non-negative integer",
+            )
+        })
+}
+
+fn parse_port(port: Option<&Value>) -> Option<u16> {
+    port.and_then(Value::as_u64)
+        .filter(|port| (1..=65535).contains(port))
+        .map(|port| port as u16)
+}
+
+/// Accepts DNS names and IPv4/IPv6 addresses, rejecting anything that can't appear in a URL host.
+fn is_valid_hostname(hostname: &str) -> bool {
+    !hostname.is_empty()
+        && hostname.len() <= 253
+        && hostname
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'))
+}
+
+fn random_token(length: usize) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+    (0..length)
+        .map(|_| ALPHABET[(random_u64() % ALPHABET.len() as u64) as usize] as char)
+        .collect()
+}
+
+fn random_server_id() -> String {
+    let hex = format!("{:016x}{:016x}", random_u64(), random_u64());
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Serves a single request, then closes the connection.
+fn serve_connection(
+    socket: TcpStream,
+    tls_config: Arc<ServerConfig>,
+    prefix: &str,
+    state: &Mutex<State>,
+) -> io::Result<()> {
+    socket.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let connection = ServerConnection::new(tls_config)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    let mut stream = StreamOwned::new(connection, socket);
+
+    let Some(Request {
+        method,
+        target,
+        body,
+    }) = read_request(&mut stream, MAX_REQUEST_SIZE)?
+    else {
+        return Ok(());
+    };
+
+    let response = {
+        let mut state = state.lock().unwrap_or_else(|err| err.into_inner());
+        match target.strip_prefix(prefix) {
+            Some(path) if path.is_empty() || path.starts_with('/') => {
+                let path = path.split('?').next().unwrap_or_default().to_string();
+                state.requests.push(MockRequest {
+                    method: method.clone(),
+                    path: path.clone(),
+                    body: body.clone(),
+                });
+                state.handle(&method, &path, &body)
+            }
+            // Without the secret the API is invisible, like on a real server
+            _ => Response::error(
+                404,
+                "ResourceNotFound",
+                format!("{} does not exist", target),
+            ),
+        }
+    };
+
+    let body = response
+        .body
+        .map(|body| body.to_string())
+        .unwrap_or_default();
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason_phrase(response.status),
+        body.len()
+    );
+    if !body.is_empty() {
+        head.push_str("Content-Type: application/json\r\n");
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    stream.conn.send_close_notify();
+    stream.flush()
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        500 => "Internal Server Error",
+        _ => "Unknown",
+    }
+}
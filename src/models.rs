@@ -0,0 +1,278 @@
+//! Typed representations of the Outline VPN Management API requests and responses.
+//!
+//! Field names follow the schemas from `api.yml`, converted to snake case. Every struct keeps
+//! the fields it does not model yet in `extra`, so newer server versions can still be inspected
+//! without waiting for a crate release.
+
+use crate::{ByteSize, ShadowsocksUrl, ShadowsocksUrlError};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroU16;
+
+/// Raw JSON fields that are present in a response but are not modelled by the crate.
+pub type ExtraFields = serde_json::Map<String, serde_json::Value>;
+
+/// Characters left as is in a path segment: the RFC 3986 unreserved set.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// ID of an access key.
+///
+/// The API defines key IDs as strings. Servers number keys (`"0"`, `"1"`, ...) by default but
+/// also accept custom IDs such as UUIDs, so any string is a valid ID. Integers convert into
+/// `AccessKeyId` for convenience, and the ID is percent-encoded when it's used in a request path.
+///
+/// ```rust
+/// use outline_api::AccessKeyId;
+///
+/// assert_eq!(AccessKeyId::from(7u16), "7");
+/// assert_eq!(AccessKeyId::from("customer/42").as_str(), "customer/42");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AccessKeyId(String);
+
+impl AccessKeyId {
+    /// Creates an access key ID from any string.
+    pub fn new(id: impl Into<String>) -> Self {
+        AccessKeyId(id.into())
+    }
+
+    /// Returns the ID as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the ID percent-encoded for use as a single URL path segment.
+    pub(crate) fn to_path_segment(&self) -> String {
+        utf8_percent_encode(&self.0, PATH_SEGMENT).to_string()
+    }
+}
+
+impl fmt::Display for AccessKeyId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for AccessKeyId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for AccessKeyId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for AccessKeyId {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for AccessKeyId {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl From<String> for AccessKeyId {
+    fn from(id: String) -> Self {
+        AccessKeyId(id)
+    }
+}
+
+impl From<&String> for AccessKeyId {
+    fn from(id: &String) -> Self {
+        AccessKeyId(id.clone())
+    }
+}
+
+impl From<&str> for AccessKeyId {
+    fn from(id: &str) -> Self {
+        AccessKeyId(id.to_string())
+    }
+}
+
+impl From<&AccessKeyId> for AccessKeyId {
+    fn from(id: &AccessKeyId) -> Self {
+        id.clone()
+    }
+}
+
+macro_rules! impl_access_key_id_from_integer {
+    ($($integer:ty),*) => {
+        $(
+            impl From<$integer> for AccessKeyId {
+                fn from(id: $integer) -> Self {
+                    AccessKeyId(id.to_string())
+                }
+            }
+
+            impl From<&$integer> for AccessKeyId {
+                fn from(id: &$integer) -> Self {
+                    AccessKeyId(id.to_string())
+                }
+            }
+        )*
+    };
+}
+
+impl_access_key_id_from_integer!(u8, u16, u32, u64, usize);
+
+/// Server information returned by `GET /server`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Server {
+    pub name: String,
+    pub server_id: String,
+    pub metrics_enabled: bool,
+    pub created_timestamp_ms: u64,
+    pub version: String,
+    /// Data limit applied to every access key, if one is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_key_data_limit: Option<DataLimit>,
+    pub port_for_new_access_keys: u16,
+    pub hostname_for_access_keys: String,
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// Data transfer limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataLimit {
+    pub bytes: ByteSize,
+}
+
+impl DataLimit {
+    /// Creates a limit from a `ByteSize` or a number of bytes.
+    pub fn new(bytes: impl Into<ByteSize>) -> Self {
+        DataLimit {
+            bytes: bytes.into(),
+        }
+    }
+}
+
+/// A single access key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessKey {
+    pub id: AccessKeyId,
+    pub name: String,
+    pub password: String,
+    pub port: u16,
+    pub method: String,
+    /// Per-key data limit, if one is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_limit: Option<DataLimit>,
+    /// `ss://` URL that can be imported by Outline clients.
+    pub access_url: String,
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+impl AccessKey {
+    /// Parses `access_url`, e.g. to show the method, host and port or to rewrite the host.
+    pub fn shadowsocks_url(&self) -> Result<ShadowsocksUrl, ShadowsocksUrlError> {
+        self.access_url.parse()
+    }
+}
+
+/// List of access keys returned by `GET /access-keys`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessKeyList {
+    pub access_keys: Vec<AccessKey>,
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// Data transferred by each access key, returned by `GET /metrics/transfer`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferMetrics {
+    /// Bytes transferred, keyed by access key ID.
+    pub bytes_transferred_by_user_id: HashMap<AccessKeyId, u64>,
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// 'Share anonymous metrics' setting returned by `GET /metrics/enabled`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsEnabled {
+    pub metrics_enabled: bool,
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// Options for creating an access key with `create_access_key_with_options`.
+///
+/// Every field is optional; the server picks a default for the ones left unset. `method` is
+/// accepted by all servers, the other fields require a recent server version.
+///
+/// ```rust
+/// use outline_api::{CreateAccessKeyRequest, DataLimit};
+///
+/// let request = CreateAccessKeyRequest {
+///     name: Some("Alice".to_string()),
+///     method: Some("aes-192-gcm".to_string()),
+///     limit: Some(DataLimit::new("10 GB".parse::<outline_api::ByteSize>().unwrap())),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreateAccessKeyRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Encryption method, e.g. `chacha20-ietf-poly1305` or `aes-192-gcm`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// Port the key listens on. Port `0` is not representable, so it can't reach the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<NonZeroU16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<DataLimit>,
+}
+
+/// Body of the requests renaming the server or an access key.
+#[derive(Serialize)]
+pub(crate) struct NameRequest<'a> {
+    pub(crate) name: &'a str,
+}
+
+/// Body of `PUT /server/hostname-for-access-keys`.
+#[derive(Serialize)]
+pub(crate) struct HostnameRequest<'a> {
+    pub(crate) hostname: &'a str,
+}
+
+/// Body of `PUT /server/port-for-new-access-keys`.
+#[derive(Serialize)]
+pub(crate) struct PortRequest {
+    pub(crate) port: NonZeroU16,
+}
+
+/// Body of the requests setting a data limit.
+#[derive(Serialize)]
+pub(crate) struct DataLimitRequest {
+    pub(crate) limit: DataLimit,
+}
+
+/// Body of `PUT /metrics/enabled`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MetricsEnabledRequest {
+    pub(crate) metrics_enabled: bool,
+}
@@ -0,0 +1,167 @@
+//! QR codes of access URLs.
+
+use crate::AccessKey;
+use qrcode::render::{svg, unicode};
+use qrcode::{Color, EcLevel};
+use std::fmt;
+
+/// Width of the blank border around the code, in modules, as required by the QR code spec.
+const QUIET_ZONE: usize = 4;
+
+/// Largest side of a rendered image, in pixels. Keeps the PNG pixel buffer of the largest codes
+/// under 256 MiB.
+const MAX_IMAGE_SIDE: usize = 16 * 1024;
+
+/// A QR code, usually of an access URL, that can be rendered as PNG, SVG or Unicode text.
+///
+/// Available with the `qr` feature.
+///
+/// ```rust
+/// use outline_api::QrCode;
+///
+/// let code = QrCode::new("ss://Y2hhY2hhMjAtaWV0Zi1wb2x5MTMwNTpzZWNyZXQ=@1.2.3.4:12345/?outline=1").unwrap();
+///
+/// // For terminals
+/// println!("{}", code.to_unicode());
+/// // For web pages
+/// let svg = code.to_svg(8).unwrap();
+/// let png = code.to_png(8).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct QrCode {
+    code: qrcode::QrCode,
+}
+
+/// Error returned when the data doesn't fit in a QR code, or the image would be too large.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrError(QrErrorKind);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum QrErrorKind {
+    Encode(qrcode::types::QrError),
+    ImageTooLarge { module_size: u32 },
+}
+
+impl fmt::Display for QrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            QrErrorKind::Encode(err) => write!(f, "Can't encode the QR code: {}", err),
+            QrErrorKind::ImageTooLarge { module_size } => write!(
+                f,
+                "A module size of {} pixels makes the image wider than {} pixels",
+                module_size, MAX_IMAGE_SIDE
+            ),
+        }
+    }
+}
+
+impl std::error::Error for QrError {}
+
+impl fmt::Debug for QrCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("QrCode")
+            .field("width", &self.code.width())
+            .finish_non_exhaustive()
+    }
+}
+
+impl QrCode {
+    /// Encodes `data` with medium error correction, which phone cameras read reliably.
+    pub fn new(data: impl AsRef<[u8]>) -> Result<Self, QrError> {
+        let code = qrcode::QrCode::with_error_correction_level(data, EcLevel::M)
+            .map_err(|err| QrError(QrErrorKind::Encode(err)))?;
+        Ok(QrCode { code })
+    }
+
+    /// Encodes the `access_url` of an access key.
+    pub fn from_access_key(key: &AccessKey) -> Result<Self, QrError> {
+        QrCode::new(&key.access_url)
+    }
+
+    /// Returns the number of modules on each side, without the quiet zone.
+    pub fn width(&self) -> usize {
+        self.code.width()
+    }
+
+    /// Renders the code as Unicode half blocks, two rows per line, for terminals. Colors are
+    /// inverted so the code reads correctly on the usual light-on-dark terminal.
+    pub fn to_unicode(&self) -> String {
+        self.code
+            .render::<unicode::Dense1x2>()
+            .dark_color(unicode::Dense1x2::Light)
+            .light_color(unicode::Dense1x2::Dark)
+            .build()
+    }
+
+    /// Renders the code as an SVG document, with each module `module_size` pixels wide.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the image would be more than 16384 pixels wide.
+    pub fn to_svg(&self, module_size: u32) -> Result<String, QrError> {
+        let module_size = module_size.max(1);
+        self.image_side(module_size)?;
+        Ok(self
+            .code
+            .render::<svg::Color>()
+            .module_dimensions(module_size, module_size)
+            .build())
+    }
+
+    /// Renders the code as a grayscale PNG image, with each module `module_size` pixels wide.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the image would be more than 16384 pixels wide.
+    pub fn to_png(&self, module_size: u32) -> Result<Vec<u8>, QrError> {
+        let module_size = module_size.max(1);
+        let side = self.image_side(module_size)?;
+        let modules = self.code.width();
+        let module_size = module_size as usize;
+        let colors = self.code.to_colors();
+
+        let mut pixels = vec![0xffu8; side * side];
+        for (index, color) in colors.iter().enumerate() {
+            if *color != Color::Dark {
+                continue;
+            }
+            let x = (index % modules + QUIET_ZONE) * module_size;
+            let y = (index / modules + QUIET_ZONE) * module_size;
+            for row in y..y + module_size {
+                let start = row * side + x;
+                pixels[start..start + module_size].fill(0);
+            }
+        }
+
+        let mut png = Vec::new();
+        // `side` is at most `MAX_IMAGE_SIDE`, so it fits in a `u32`
+        let mut encoder = png::Encoder::new(&mut png, side as u32, side as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        // Writing to a `Vec` can't fail and the image dimensions match the pixel buffer
+        let mut writer = encoder
+            .write_header()
+            .expect("PNG header should be written to memory");
+        writer
+            .write_image_data(&pixels)
+            .expect("PNG data should be written to memory");
+        writer.finish().expect("PNG should be finished in memory");
+        Ok(png)
+    }
+
+    /// Returns the side of the image in pixels, quiet zone included, if it's at most
+    /// `MAX_IMAGE_SIDE`.
+    fn image_side(&self, module_size: u32) -> Result<usize, QrError> {
+        (self.code.width() + 2 * QUIET_ZONE)
+            .checked_mul(module_size as usize)
+            .filter(|side| *side <= MAX_IMAGE_SIDE)
+            .ok_or(QrError(QrErrorKind::ImageTooLarge { module_size }))
+    }
+}
+
+impl AccessKey {
+    /// Encodes `access_url` as a QR code. Available with the `qr` feature.
+    pub fn qr_code(&self) -> Result<QrCode, QrError> {
+        QrCode::from_access_key(self)
+    }
+}
@@ -0,0 +1,351 @@
+//! Periodic data quotas on top of Outline's absolute data limits.
+//!
+//! Outline limits the bytes a key may transfer in the server's rolling window, with no notion of
+//! billing periods. A `QuotaEngine` turns a periodic allowance ("50 GB per month, resetting on the
+//! 1st") into the absolute limit to set on each key, from the transfer history kept by a
+//! `Recorder`. Run it regularly: the limits it computes grow back when a period rolls over.
+//!
+//! ```rust
+//! # #[cfg(feature = "blocking")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let server = outline_api::mock::MockServer::start();
+//! # let vpn = server.client();
+//! # let path = std::env::temp_dir().join(format!("outline-quota-doc-{}.jsonl", std::process::id()));
+//! use outline_api::{ByteSize, Quota, QuotaEngine, Recorder};
+//!
+//! let engine = QuotaEngine::new().default_quota(Quota::monthly("50 GB".parse::<ByteSize>()?).with_reset_day(15)?);
+//!
+//! let plan = engine.plan_for(&vpn, &Recorder::new(&path))?;
+//! println!("{}", plan);
+//! for outcome in plan.apply(&vpn) {
+//!     if let Err(err) = outcome.result {
+//!         eprintln!("{} failed: {}", outcome.operation, err);
+//!     }
+//! }
+//! # std::fs::remove_file(&path)?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "blocking"))]
+//! # fn main() {}
+//! ```
+
+use crate::reconcile::{Operation, Plan};
+use crate::{APIError, AccessKey, AccessKeyId, ByteSize, DataLimit, Snapshot};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// An amount of data allowed per month, resetting at 00:00 UTC on the reset day.
+///
+/// Reset days past the end of a shorter month, such as the 31st, fall on its last day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quota {
+    allowance: ByteSize,
+    #[serde(default = "first_day", deserialize_with = "deserialize_reset_day")]
+    reset_day: u8,
+}
+
+fn first_day() -> u8 {
+    1
+}
+
+fn deserialize_reset_day<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    validate_reset_day(u8::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn validate_reset_day(day: u8) -> Result<u8, QuotaError> {
+    if (1..=31).contains(&day) {
+        Ok(day)
+    } else {
+        Err(QuotaError::InvalidResetDay(day))
+    }
+}
+
+impl Quota {
+    /// Creates a quota of `allowance` per month, resetting on the 1st.
+    pub fn monthly(allowance: impl Into<ByteSize>) -> Self {
+        Quota {
+            allowance: allowance.into(),
+            reset_day: first_day(),
+        }
+    }
+
+    /// Sets the day of the month the period starts on, from 1 through 31.
+    ///
+    /// # Errors
+    ///
+    /// Returns `QuotaError::InvalidResetDay` if `day` is outside that range.
+    pub fn with_reset_day(mut self, day: u8) -> Result<Self, QuotaError> {
+        self.reset_day = validate_reset_day(day)?;
+        Ok(self)
+    }
+
+    /// Returns the data allowed per period.
+    pub fn allowance(&self) -> ByteSize {
+        self.allowance
+    }
+
+    /// Returns the day of the month the period starts on, from 1 through 31.
+    pub fn reset_day(&self) -> u8 {
+        self.reset_day
+    }
+
+    /// Returns the start of the period containing `now`.
+    pub fn period_start(&self, now: SystemTime) -> SystemTime {
+        let today = unix_days(now);
+        let (year, month, day) = civil_from_days(today);
+        let (year, month) = if day >= self.day_in(year, month) {
+            (year, month)
+        } else {
+            previous_month(year, month)
+        };
+        self.reset_time(year, month)
+    }
+
+    /// Returns the start of the period following the one containing `now`.
+    pub fn next_reset(&self, now: SystemTime) -> SystemTime {
+        let (year, month, day) = civil_from_days(unix_days(now));
+        let (year, month) = if day < self.day_in(year, month) {
+            (year, month)
+        } else {
+            next_month(year, month)
+        };
+        self.reset_time(year, month)
+    }
+
+    /// Returns the reset day of the given month, clamped to its length.
+    fn day_in(&self, year: i64, month: u32) -> u32 {
+        u32::from(self.reset_day).min(days_in_month(year, month))
+    }
+
+    fn reset_time(&self, year: i64, month: u32) -> SystemTime {
+        let days = days_from_civil(year, month, self.day_in(year, month));
+        UNIX_EPOCH + Duration::from_secs(days.max(0) as u64 * SECONDS_PER_DAY)
+    }
+}
+
+/// Computes the data limits that enforce periodic quotas.
+///
+/// The limit of a key is the bytes the server counted for it at the start of the period plus the
+/// allowance. As the server only counts traffic over a rolling window, it's computed as the
+/// current count plus the allowance left, which also stays right when old traffic leaves the
+/// window.
+///
+/// Keys without a quota keep their data limits, so limits set by hand or by a `Reconciler` are
+/// left alone. With `remove_unlisted_limits(true)`, the engine owns the data limits of every key
+/// instead: keys without a quota, such as one whose quota was removed from the config, have their
+/// limit removed.
+#[derive(Debug, Clone, Default)]
+pub struct QuotaEngine {
+    quotas: BTreeMap<AccessKeyId, Quota>,
+    default_quota: Option<Quota>,
+    remove_unlisted_limits: bool,
+}
+
+impl QuotaEngine {
+    /// Creates an engine without quotas.
+    pub fn new() -> Self {
+        QuotaEngine::default()
+    }
+
+    /// Sets whether keys without a quota have their data limit removed. Defaults to `false`, which
+    /// leaves their limits alone, e.g. to manage them by hand.
+    pub fn remove_unlisted_limits(mut self, remove_unlisted_limits: bool) -> Self {
+        self.remove_unlisted_limits = remove_unlisted_limits;
+        self
+    }
+
+    /// Sets the quota of a key, overriding the default quota.
+    pub fn with_quota(mut self, id: impl Into<AccessKeyId>, quota: Quota) -> Self {
+        self.quotas.insert(id.into(), quota);
+        self
+    }
+
+    /// Sets the quota of keys that don't have their own.
+    pub fn default_quota(mut self, quota: Quota) -> Self {
+        self.default_quota = Some(quota);
+        self
+    }
+
+    /// Returns the quota applying to a key.
+    pub fn quota_for(&self, id: &AccessKeyId) -> Option<Quota> {
+        self.quotas.get(id).copied().or(self.default_quota)
+    }
+
+    /// Computes the limit changes at `now` from the recorded snapshots, sorted by time, and the
+    /// keys on the server.
+    ///
+    /// The last snapshot is taken as the current count of each key, so it should be recorded right
+    /// before planning. Keys with a quota that are missing from it are skipped.
+    pub fn plan(&self, snapshots: &[Snapshot], keys: &[AccessKey], now: SystemTime) -> Plan {
+        let mut usage_by_period = HashMap::new();
+        let mut operations = Vec::new();
+
+        for key in keys {
+            let Some(quota) = self.quota_for(&key.id) else {
+                if let Some(limit) = key.data_limit.filter(|_| self.remove_unlisted_limits) {
+                    operations.push(Operation::RemoveLimit {
+                        id: key.id.clone(),
+                        from: limit,
+                    });
+                }
+                continue;
+            };
+            let current = snapshots
+                .last()
+                .and_then(|snapshot| snapshot.bytes_transferred.get(&key.id));
+            let Some(current) = current else {
+                continue;
+            };
+
+            let period_start = quota.period_start(now);
+            let usage = usage_by_period
+                .entry(period_start)
+                .or_insert_with(|| Snapshot::usage(snapshots, period_start, now));
+            let used = usage.get(&key.id).copied().unwrap_or(0);
+            let left = quota.allowance.as_u64().saturating_sub(used);
+            let limit = DataLimit::new(current.saturating_add(left));
+
+            if key.data_limit != Some(limit) {
+                operations.push(Operation::SetLimit {
+                    id: key.id.clone(),
+                    from: key.data_limit,
+                    to: limit,
+                });
+            }
+        }
+
+        Plan { operations }
+    }
+
+    /// Lists the keys of the server, records a snapshot of their counts with `recorder`, then
+    /// computes the plan against the recorded history.
+    #[cfg(feature = "blocking")]
+    pub fn plan_for(
+        &self,
+        vpn: &crate::blocking::OutlineVPN,
+        recorder: &crate::Recorder,
+    ) -> Result<Plan, QuotaError> {
+        let keys = vpn.list_access_keys()?.access_keys;
+        let metrics = vpn.get_each_access_key_data_transferred()?;
+        let now = SystemTime::now();
+        recorder.append(&Snapshot::new(now, &keys, &metrics, &recorder.salt()?))?;
+        Ok(self.plan(&recorder.snapshots()?, &keys, now))
+    }
+}
+
+/// Errors returned by `QuotaEngine::plan_for` and `Quota::with_reset_day`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum QuotaError {
+    /// The server request failed.
+    Api(APIError),
+    /// The transfer history couldn't be read or written.
+    Recorder(io::Error),
+    /// The reset day is outside 1 through 31.
+    InvalidResetDay(u8),
+}
+
+impl fmt::Display for QuotaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QuotaError::Api(err) => write!(f, "{}", err),
+            QuotaError::Recorder(err) => write!(f, "Transfer history error: {}", err),
+            QuotaError::InvalidResetDay(day) => {
+                write!(f, "Invalid reset day {}: it must be from 1 through 31", day)
+            }
+        }
+    }
+}
+
+impl std::error::Error for QuotaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            QuotaError::Api(err) => Some(err),
+            QuotaError::Recorder(err) => Some(err),
+            QuotaError::InvalidResetDay(_) => None,
+        }
+    }
+}
+
+impl From<APIError> for QuotaError {
+    fn from(err: APIError) -> Self {
+        QuotaError::Api(err)
+    }
+}
+
+impl From<io::Error> for QuotaError {
+    fn from(err: io::Error) -> Self {
+        QuotaError::Recorder(err)
+    }
+}
+
+fn unix_days(time: SystemTime) -> i64 {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    (seconds / SECONDS_PER_DAY) as i64
+}
+
+fn previous_month(year: i64, month: u32) -> (i64, u32) {
+    if month == 1 {
+        (year - 1, 12)
+    } else {
+        (year, month - 1)
+    }
+}
+
+fn next_month(year: i64, month: u32) -> (i64, u32) {
+    if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    }
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date, after Howard Hinnant's algorithm.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Proleptic Gregorian date of a number of days since 1970-01-01, the inverse of
+/// `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,379 @@
+//! Declarative management of access keys.
+//!
+//! A `Reconciler` compares the desired keys (for example loaded from a config file) with the keys
+//! on the server and computes a `Plan` of operations that makes the server match. The plan can be
+//! printed as a dry run, then applied with the per-key methods of `OutlineVPN`.
+//!
+//! ```rust
+//! # #[cfg(feature = "blocking")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let server = outline_api::mock::MockServer::start();
+//! # let vpn = server.client();
+//! use outline_api::{DesiredKey, Reconciler};
+//!
+//! let desired: Vec<DesiredKey> = serde_json::from_str(r#"[
+//!     {"name": "Alice", "limit": {"bytes": 10000000000}},
+//!     {"id": "bob", "name": "Bob"}
+//! ]"#).unwrap();
+//!
+//! let plan = Reconciler::new(desired)?.plan_for(&vpn)?;
+//! println!("{}", plan);
+//!
+//! for outcome in plan.apply(&vpn) {
+//!     if let Err(err) = outcome.result {
+//!         eprintln!("{} failed: {}", outcome.operation, err);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "blocking"))]
+//! # fn main() {}
+//! ```
+
+use crate::{APIError, AccessKey, AccessKeyId, DataLimit};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+/// An access key as it should exist on the server.
+///
+/// Keys with an `id` are matched by ID, and created with that ID if they don't exist (which
+/// requires a server supporting `PUT /access-keys/{id}`). Keys without an `id` are matched by
+/// name. A `limit` of `None` means the key must not have a data limit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DesiredKey {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<AccessKeyId>,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<DataLimit>,
+}
+
+impl DesiredKey {
+    /// Creates a key matched by name, without a data limit.
+    pub fn named(name: impl Into<String>) -> Self {
+        DesiredKey {
+            id: None,
+            name: name.into(),
+            limit: None,
+        }
+    }
+
+    /// Creates a key matched by ID, without a data limit.
+    pub fn with_id(id: impl Into<AccessKeyId>, name: impl Into<String>) -> Self {
+        DesiredKey {
+            id: Some(id.into()),
+            name: name.into(),
+            limit: None,
+        }
+    }
+
+    /// Sets the data limit of the key.
+    pub fn limit(mut self, limit: DataLimit) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+/// A single change to the keys of a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// Create a key, with the server picking the ID unless `id` is set.
+    Create {
+        id: Option<AccessKeyId>,
+        name: String,
+        limit: Option<DataLimit>,
+    },
+    /// Rename a key.
+    Rename {
+        id: AccessKeyId,
+        from: String,
+        to: String,
+    },
+    /// Set or change the data limit of a key.
+    SetLimit {
+        id: AccessKeyId,
+        from: Option<DataLimit>,
+        to: DataLimit,
+    },
+    /// Remove the data limit of a key.
+    RemoveLimit { id: AccessKeyId, from: DataLimit },
+    /// Delete a key that is not in the desired set.
+    Delete { id: AccessKeyId, name: String },
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operation::Create { id, name, limit } => {
+                write!(f, "+ create key {:?}", name)?;
+                if let Some(id) = id {
+                    write!(f, " with ID {:?}", id.as_str())?;
+                }
+                if let Some(limit) = limit {
+                    write!(f, ", limit {}", limit.bytes)?;
+                }
+                Ok(())
+            }
+            Operation::Rename { id, from, to } => {
+                write!(
+                    f,
+                    "~ rename key {:?} from {:?} to {:?}",
+                    id.as_str(),
+                    from,
+                    to
+                )
+            }
+            Operation::SetLimit { id, from, to } => match from {
+                Some(from) => write!(
+                    f,
+                    "~ change limit of key {:?} from {} to {}",
+                    id.as_str(),
+                    from.bytes,
+                    to.bytes
+                ),
+                None => write!(f, "~ set limit of key {:?} to {}", id.as_str(), to.bytes),
+            },
+            Operation::RemoveLimit { id, from } => write!(
+                f,
+                "~ remove limit of {} from key {:?}",
+                from.bytes,
+                id.as_str()
+            ),
+            Operation::Delete { id, name } => {
+                write!(f, "- delete key {:?} ({:?})", id.as_str(), name)
+            }
+        }
+    }
+}
+
+/// Error returned by `Reconciler::new` for a desired set it can't plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReconcileError {
+    /// Several desired keys have this ID.
+    DuplicateId(AccessKeyId),
+}
+
+impl fmt::Display for ReconcileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReconcileError::DuplicateId(id) => {
+                write!(f, "Several desired keys have the ID {:?}.", id.as_str())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReconcileError {}
+
+/// Computes the operations that make the server keys match the desired keys.
+#[derive(Debug, Clone)]
+pub struct Reconciler {
+    desired: Vec<DesiredKey>,
+    delete_unlisted: bool,
+}
+
+impl Reconciler {
+    /// Creates a reconciler for the given desired keys.
+    ///
+    /// By default, keys on the server that don't match any desired key are deleted.
+    ///
+    /// # Errors
+    ///
+    /// - `ReconcileError::DuplicateId`: If several desired keys have the same ID, as only one of
+    ///   them could exist on the server. Keys without an ID may share a name.
+    pub fn new(desired: impl IntoIterator<Item = DesiredKey>) -> Result<Self, ReconcileError> {
+        let desired: Vec<DesiredKey> = desired.into_iter().collect();
+        let mut ids = HashSet::new();
+        if let Some(id) = desired
+            .iter()
+            .filter_map(|key| key.id.as_ref())
+            .find(|id| !ids.insert(*id))
+        {
+            return Err(ReconcileError::DuplicateId(id.clone()));
+        }
+
+        Ok(Reconciler {
+            desired,
+            delete_unlisted: true,
+        })
+    }
+
+    /// Sets whether keys that don't match any desired key are deleted.
+    pub fn delete_unlisted(mut self, delete_unlisted: bool) -> Self {
+        self.delete_unlisted = delete_unlisted;
+        self
+    }
+
+    /// Computes the plan against the given server keys.
+    ///
+    /// Desired keys with an ID are matched first. The remaining ones are then matched by name,
+    /// each consuming one unmatched server key with that name, so duplicate names are supported.
+    pub fn plan(&self, existing: &[AccessKey]) -> Plan {
+        let mut matched = vec![false; existing.len()];
+        let mut matches: Vec<Option<usize>> = vec![None; self.desired.len()];
+
+        for (desired_index, desired) in self.desired.iter().enumerate() {
+            if let Some(id) = &desired.id {
+                let found = existing
+                    .iter()
+                    .enumerate()
+                    .position(|(index, key)| !matched[index] && key.id == *id);
+                if let Some(index) = found {
+                    matched[index] = true;
+                    matches[desired_index] = Some(index);
+                }
+            }
+        }
+        for (desired_index, desired) in self.desired.iter().enumerate() {
+            if desired.id.is_some() {
+                continue;
+            }
+            let found = existing
+                .iter()
+                .enumerate()
+                .position(|(index, key)| !matched[index] && key.name == desired.name);
+            if let Some(index) = found {
+                matched[index] = true;
+                matches[desired_index] = Some(index);
+            }
+        }
+
+        let mut operations = Vec::new();
+        for (desired, found) in self.desired.iter().zip(matches) {
+            let key = match found {
+                Some(index) => &existing[index],
+                None => {
+                    operations.push(Operation::Create {
+                        id: desired.id.clone(),
+                        name: desired.name.clone(),
+                        limit: desired.limit,
+                    });
+                    continue;
+                }
+            };
+            if key.name != desired.name {
+                operations.push(Operation::Rename {
+                    id: key.id.clone(),
+                    from: key.name.clone(),
+                    to: desired.name.clone(),
+                });
+            }
+            match (key.data_limit, desired.limit) {
+                (current, Some(limit)) if current != Some(limit) => {
+                    operations.push(Operation::SetLimit {
+                        id: key.id.clone(),
+                        from: current,
+                        to: limit,
+                    })
+                }
+                (Some(current), None) => operations.push(Operation::RemoveLimit {
+                    id: key.id.clone(),
+                    from: current,
+                }),
+                _ => {}
+            }
+        }
+
+        if self.delete_unlisted {
+            for (key, _) in existing.iter().zip(matched).filter(|(_, matched)| !matched) {
+                operations.push(Operation::Delete {
+                    id: key.id.clone(),
+                    name: key.name.clone(),
+                });
+            }
+        }
+
+        Plan { operations }
+    }
+
+    /// Lists the keys of the server and computes the plan against them.
+    #[cfg(feature = "blocking")]
+    pub fn plan_for(&self, vpn: &crate::blocking::OutlineVPN) -> Result<Plan, APIError> {
+        Ok(self.plan(&vpn.list_access_keys()?.access_keys))
+    }
+}
+
+/// Operations computed by a `Reconciler`, in the order they are applied.
+///
+/// `Display` prints one operation per line, as a dry run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub operations: Vec<Operation>,
+}
+
+impl Plan {
+    /// Returns `true` if the server already matches the desired keys.
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Applies every operation, continuing after failures, and returns the result of each one.
+    #[cfg(feature = "blocking")]
+    pub fn apply(&self, vpn: &crate::blocking::OutlineVPN) -> Vec<Outcome> {
+        self.operations
+            .iter()
+            .map(|operation| Outcome {
+                operation: operation.clone(),
+                result: apply_operation(vpn, operation),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.operations.is_empty() {
+            return f.write_str("No changes");
+        }
+        for (index, operation) in self.operations.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", operation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of applying one operation of a `Plan`.
+#[derive(Debug)]
+pub struct Outcome {
+    pub operation: Operation,
+    pub result: Result<(), APIError>,
+}
+
+#[cfg(feature = "blocking")]
+fn apply_operation(
+    vpn: &crate::blocking::OutlineVPN,
+    operation: &Operation,
+) -> Result<(), APIError> {
+    match operation {
+        Operation::Create { id, name, limit } => {
+            let request = crate::CreateAccessKeyRequest {
+                name: Some(name.clone()),
+                limit: *limit,
+                ..Default::default()
+            };
+            let key = match id {
+                Some(id) => vpn.create_access_key_with_id(id, &request)?,
+                None => vpn.create_access_key_with_options(&request)?,
+            };
+            // Older servers ignore the name and limit of new keys, so set them one by one
+            if key.name != *name {
+                vpn.change_name_for_access_key(&key.id, name)?;
+            }
+            if let Some(limit) = limit {
+                if key.data_limit != Some(*limit) {
+                    vpn.set_data_transfer_limit_by_id(&key.id, limit.bytes)?;
+                }
+            }
+            Ok(())
+        }
+        Operation::Rename { id, to, .. } => vpn.change_name_for_access_key(id, to),
+        Operation::SetLimit { id, to, .. } => vpn.set_data_transfer_limit_by_id(id, to.bytes),
+        Operation::RemoveLimit { id, .. } => vpn.del_data_transfer_limit_by_id(id),
+        Operation::Delete { id, .. } => vpn.delete_access_key_by_id(id),
+    }
+}
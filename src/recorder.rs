@@ -0,0 +1,286 @@
+//! Time series of the data transferred by access keys.
+//!
+//! `GET /metrics/transfer` only returns totals over the server's rolling window. A `Recorder`
+//! appends timestamped `Snapshot`s of these totals to a file, one JSON object per line, so the
+//! usage of each key over any period can be computed later with `Snapshot::usage`.
+//!
+//! ```rust
+//! # #[cfg(feature = "blocking")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let server = outline_api::mock::MockServer::start();
+//! # let vpn = server.client();
+//! # let path = std::env::temp_dir().join(format!("outline-recorder-doc-{}.jsonl", std::process::id()));
+//! use outline_api::{Recorder, Snapshot};
+//! use std::time::{Duration, SystemTime};
+//!
+//! let recorder = Recorder::new(&path);
+//! recorder.append(&Snapshot::take(&vpn, &recorder.salt()?)?)?;
+//!
+//! // Usage of each key over the last day
+//! let now = SystemTime::now();
+//! for (id, bytes) in recorder.usage(now - Duration::from_secs(24 * 60 * 60), now)? {
+//!     println!("{}: {} bytes", id.as_str(), bytes);
+//! }
+//! # std::fs::remove_file(&path)?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "blocking"))]
+//! # fn main() {}
+//! ```
+
+use crate::{random_u64, AccessKey, AccessKeyId, TransferMetrics};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Bytes transferred by each access key at one point in time.
+///
+/// Every key existing on the server has an entry, with `0` for keys that haven't transferred
+/// anything yet. A key deleted and recreated with the same ID is told apart from a key whose total
+/// dropped as old traffic left the server's rolling window by its fingerprint, or by missing from
+/// a snapshot taken while it didn't exist.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    pub bytes_transferred: BTreeMap<AccessKeyId, u64>,
+    /// Salted hash of the password of each key, which changes when a key is recreated with the
+    /// same ID. Snapshots recorded before fingerprints were added have none.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub key_fingerprints: BTreeMap<AccessKeyId, String>,
+    /// Random salt of the fingerprints, shared by the snapshots of a file so they can be compared.
+    /// Fingerprints recorded before salts were added have none.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub fingerprint_salt: String,
+}
+
+impl Snapshot {
+    /// Creates a snapshot of the given keys from the transfer metrics of their server, with their
+    /// fingerprints salted with `salt`, usually `Recorder::salt`.
+    ///
+    /// Metrics of keys that are not in `keys`, such as recently deleted ones, are left out.
+    pub fn new(
+        timestamp: SystemTime,
+        keys: &[AccessKey],
+        metrics: &TransferMetrics,
+        salt: &str,
+    ) -> Self {
+        Snapshot {
+            timestamp_ms: unix_ms(timestamp),
+            bytes_transferred: keys
+                .iter()
+                .map(|key| {
+                    let bytes = metrics.bytes_transferred_by_user_id.get(&key.id);
+                    (key.id.clone(), bytes.copied().unwrap_or(0))
+                })
+                .collect(),
+            key_fingerprints: keys
+                .iter()
+                .map(|key| (key.id.clone(), fingerprint(salt, &key.password)))
+                .collect(),
+            fingerprint_salt: salt.to_string(),
+        }
+    }
+
+    /// Lists the keys and their transferred bytes on the server and snapshots them now.
+    #[cfg(feature = "blocking")]
+    pub fn take(vpn: &crate::blocking::OutlineVPN, salt: &str) -> Result<Self, crate::APIError> {
+        let keys = vpn.list_access_keys()?.access_keys;
+        let metrics = vpn.get_each_access_key_data_transferred()?;
+        Ok(Snapshot::new(SystemTime::now(), &keys, &metrics, salt))
+    }
+
+    /// Returns the time the snapshot was taken.
+    pub fn timestamp(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.timestamp_ms)
+    }
+
+    /// Computes the bytes transferred by each key between `from` and `to` from sorted snapshots.
+    ///
+    /// The usage is the sum of the increases between consecutive snapshots, starting from the
+    /// last snapshot taken at or before `from` (or the first one after it, if there is none). Its
+    /// precision is therefore limited by how often snapshots were taken. Between two snapshots:
+    ///
+    /// - a key that grew is counted for the difference;
+    /// - a key that is new, that was missing from the previous snapshot, or whose fingerprint
+    ///   changed because it was deleted and recreated, is counted in full, as its counter
+    ///   restarted from zero. Fingerprints are only compared between snapshots with the same
+    ///   salt;
+    /// - a key whose total dropped, as old traffic left the server's rolling window, is counted as
+    ///   `0`.
+    ///
+    /// Every key seen after the start of the period has an entry, even if it transferred nothing.
+    pub fn usage(
+        snapshots: &[Snapshot],
+        from: SystemTime,
+        to: SystemTime,
+    ) -> BTreeMap<AccessKeyId, u64> {
+        let (from, to) = (unix_ms(from), unix_ms(to));
+        let start = snapshots
+            .iter()
+            .rposition(|snapshot| snapshot.timestamp_ms <= from)
+            .unwrap_or(0);
+        let period: Vec<&Snapshot> = snapshots[start..]
+            .iter()
+            .take_while(|snapshot| snapshot.timestamp_ms <= to)
+            .collect();
+
+        let mut totals = BTreeMap::new();
+        for pair in period.windows(2) {
+            let (previous, current) = (pair[0], pair[1]);
+            for (id, bytes) in &current.bytes_transferred {
+                let recreated = match (
+                    previous.key_fingerprints.get(id),
+                    current.key_fingerprints.get(id),
+                ) {
+                    (Some(before), Some(after)) => {
+                        previous.fingerprint_salt == current.fingerprint_salt && before != after
+                    }
+                    _ => false,
+                };
+                let increase = match previous.bytes_transferred.get(id) {
+                    Some(previous) if !recreated => bytes.saturating_sub(*previous),
+                    _ => *bytes,
+                };
+                *totals.entry(id.clone()).or_insert(0) += increase;
+            }
+        }
+        totals
+    }
+}
+
+/// Appends snapshots to a file and reads them back.
+///
+/// The file holds one JSON `Snapshot` per line and is only ever appended to, so it can be rotated
+/// or inspected with standard tools. Lines that can't be parsed, such as one cut short by a
+/// crash, are skipped when reading.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    path: PathBuf,
+}
+
+impl Recorder {
+    /// Creates a recorder for the file at `path`, which is created by the first `append`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Recorder { path: path.into() }
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends a snapshot to the file.
+    pub fn append(&self, snapshot: &Snapshot) -> io::Result<()> {
+        let mut line = serde_json::to_string(snapshot)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())
+    }
+
+    /// Reads every snapshot, sorted by time. A missing file has no snapshots.
+    pub fn snapshots(&self) -> io::Result<Vec<Snapshot>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut snapshots = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(snapshot) => snapshots.push(snapshot),
+                Err(err) => warn!(
+                    "Skipping line {} of {}: {}",
+                    index + 1,
+                    self.path.display(),
+                    err
+                ),
+            }
+        }
+        snapshots.sort_by_key(|snapshot: &Snapshot| snapshot.timestamp_ms);
+        Ok(snapshots)
+    }
+
+    /// Returns the salt of the fingerprints in the file, or a new random one if it has none yet.
+    ///
+    /// The salt is stored with every snapshot, so the first `append` of a snapshot taken with a
+    /// new salt keeps it for the following ones.
+    pub fn salt(&self) -> io::Result<String> {
+        let snapshots = self.snapshots()?;
+        let recorded = snapshots
+            .iter()
+            .rev()
+            .map(|snapshot| &snapshot.fingerprint_salt)
+            .find(|salt| !salt.is_empty());
+        Ok(match recorded {
+            Some(salt) => salt.clone(),
+            None => format!("{:016x}{:016x}", random_u64(), random_u64()),
+        })
+    }
+
+    /// Reads the snapshots and computes the usage of each key between `from` and `to`.
+    pub fn usage(
+        &self,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> io::Result<BTreeMap<AccessKeyId, u64>> {
+        Ok(Snapshot::usage(&self.snapshots()?, from, to))
+    }
+
+    /// Takes and appends a snapshot every `interval`, until the file can't be read or written.
+    ///
+    /// Failed requests to the server are logged and retried at the next interval.
+    ///
+    /// # Errors
+    ///
+    /// Only returns the file error that stopped the recorder.
+    #[cfg(feature = "blocking")]
+    pub fn run(
+        &self,
+        vpn: &crate::blocking::OutlineVPN,
+        interval: Duration,
+    ) -> io::Result<Infallible> {
+        let salt = self.salt()?;
+        loop {
+            match Snapshot::take(vpn, &salt) {
+                Ok(snapshot) => self.append(&snapshot)?,
+                Err(err) => warn!("Can't take a transfer snapshot: {}", err),
+            }
+            std::thread::sleep(interval);
+        }
+    }
+}
+
+/// Returns the first 8 bytes of the SHA-256 hash of the salted password, in hex, so the history
+/// file doesn't hold the password itself nor a hash that can be looked up in precomputed tables.
+fn fingerprint(salt: &str, password: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(password.as_bytes());
+    hasher.finalize()[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
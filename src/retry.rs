@@ -0,0 +1,162 @@
+//! Opt-in retries of idempotent requests.
+
+use crate::{is_access_key_path, random_u64, APIError, RawResponse};
+use reqwest::{Method, StatusCode};
+use std::time::Duration;
+
+/// Policy for retrying requests that failed with a transient error, set with
+/// `OutlineVPNBuilder::retry_policy`.
+///
+/// Only idempotent requests (`GET`, and the `PUT` and `DELETE` requests updating a setting) are
+/// retried. Requests creating or deleting a key are sent once: after a lost response, a retry of
+/// `create_access_key` (`POST`) would create a second key, one of `create_access_key_with_id`
+/// (`PUT /access-keys/{id}`) would fail with `APIError::AccessKeyAlreadyExists` for the key it
+/// just created, and one of `delete_access_key_by_id` (`DELETE /access-keys/{id}`) would fail with
+/// `APIError::AccessKeyInexistent` for the key it just deleted.
+///
+/// Between attempts the client waits for an exponentially growing delay, starting at
+/// `initial_backoff` and capped at `max_backoff`. Each delay is randomized between half and all
+/// of its value, so clients failing at the same time don't retry in lockstep.
+///
+/// # Examples
+///
+/// ```rust
+/// use outline_api::RetryPolicy;
+/// use reqwest::StatusCode;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new(5)
+///     .initial_backoff(Duration::from_millis(200))
+///     .max_backoff(Duration::from_secs(10))
+///     .retryable_statuses([StatusCode::INTERNAL_SERVER_ERROR, StatusCode::SERVICE_UNAVAILABLE]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    retryable_statuses: Vec<StatusCode>,
+    retry_transport_errors: bool,
+    retry_timeouts: bool,
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, starting with a 100 ms delay, retrying `429`, `500`, `502`, `503` and `504`
+    /// responses, transport errors and timeouts.
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            retryable_statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            retry_transport_errors: true,
+            retry_timeouts: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates the default policy with the given number of attempts, including the first one.
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy::default().max_attempts(max_attempts)
+    }
+
+    /// Sets the number of attempts, including the first one. Must be at least 1.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Sets the delay before the first retry.
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Sets the upper bound of the delay between two attempts.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets the response statuses that are retried, replacing the default ones.
+    pub fn retryable_statuses(mut self, statuses: impl IntoIterator<Item = StatusCode>) -> Self {
+        self.retryable_statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Sets whether requests that could not be sent or whose response could not be read
+    /// (connection refused or reset, DNS failure, ...) are retried.
+    ///
+    /// Certificate pinning failures are never retried.
+    pub fn retry_transport_errors(mut self, retry: bool) -> Self {
+        self.retry_transport_errors = retry;
+        self
+    }
+
+    /// Sets whether requests that timed out are retried.
+    pub fn retry_timeouts(mut self, retry: bool) -> Self {
+        self.retry_timeouts = retry;
+        self
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), APIError> {
+        if self.max_attempts == 0 {
+            return Err(APIError::InvalidConfig {
+                field: "retry_policy",
+                message: "max_attempts must be at least 1".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns how long to wait before retrying a request to `api_path` that got `result` on its
+    /// `attempt`-th attempt, or `None` if the result is final.
+    pub(crate) fn retry_delay(
+        &self,
+        method: &Method,
+        api_path: &str,
+        attempt: u32,
+        result: &Result<RawResponse, APIError>,
+    ) -> Option<Duration> {
+        // `PUT` and `DELETE /access-keys/{id}` create and delete the key, so they fail once they
+        // succeeded
+        let idempotent = *method == Method::GET
+            || ((*method == Method::PUT || *method == Method::DELETE)
+                && !is_access_key_path(api_path));
+        if !idempotent || attempt >= self.max_attempts || !self.is_retryable(result) {
+            return None;
+        }
+
+        Some(self.backoff(attempt))
+    }
+
+    fn is_retryable(&self, result: &Result<RawResponse, APIError>) -> bool {
+        match result {
+            Ok(response) => self.retryable_statuses.contains(&response.status),
+            Err(APIError::Transport { source, .. }) if source.is_timeout() => self.retry_timeouts,
+            Err(APIError::Transport { .. }) => self.retry_transport_errors,
+            Err(_) => false,
+        }
+    }
+
+    /// Exponential backoff with jitter: a random delay between half and all of
+    /// `initial_backoff * 2^(attempt - 1)`, capped at `max_backoff`.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .initial_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        let half = delay / 2;
+        let jitter = random_u64() % (half.as_nanos() as u64).saturating_add(1);
+
+        half + Duration::from_nanos(jitter)
+    }
+}
@@ -0,0 +1,412 @@
+//! Parsing and building of `ss://` access URLs.
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::{alphabet, Engine};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::num::NonZeroU16;
+use std::str::FromStr;
+
+/// Characters escaped in the plain `method:password` userinfo form.
+const USERINFO: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'&')
+    .add(b'+')
+    .add(b'/')
+    .add(b':')
+    .add(b';')
+    .add(b'<')
+    .add(b'=')
+    .add(b'>')
+    .add(b'?')
+    .add(b'@')
+    .add(b'[')
+    .add(b']')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'|');
+
+/// Characters escaped in query parameters.
+const QUERY: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'&')
+    .add(b'+')
+    .add(b';')
+    .add(b'<')
+    .add(b'=')
+    .add(b'>')
+    .add(b'`');
+
+/// Characters escaped in the `#tag` fragment.
+const FRAGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'`');
+
+/// Accepts both alphabets' padding conventions when decoding.
+const LENIENT: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const LENIENT_STANDARD: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, LENIENT);
+const LENIENT_URL_SAFE: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, LENIENT);
+
+/// How the `method:password` userinfo is written, kept so parsed URLs serialize unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UserInfo {
+    /// Standard base64 with padding, as written by Outline servers.
+    Base64,
+    /// URL-safe base64 without padding, as recommended by SIP002.
+    Base64Url,
+    /// Percent-encoded `method:password`, used by SIP022 (2022) methods.
+    Plain,
+}
+
+/// A Shadowsocks access URL in the [SIP002](https://shadowsocks.org/doc/sip002.html) format, such
+/// as the `access_url` of an `AccessKey`:
+/// `ss://BASE64(method:password)@host:port/?outline=1#tag`.
+///
+/// Both the base64 and the plain (`method:password`) userinfo forms are accepted, and a parsed
+/// URL serializes back in the form it was written in.
+///
+/// ```rust
+/// use outline_api::ShadowsocksUrl;
+///
+/// let url: ShadowsocksUrl = "ss://Y2hhY2hhMjAtaWV0Zi1wb2x5MTMwNTpzZWNyZXQ=@1.2.3.4:12345/?outline=1#My%20key"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(url.method(), "chacha20-ietf-poly1305");
+/// assert_eq!(url.password(), "secret");
+/// assert_eq!(url.port(), 12345);
+/// assert_eq!(url.tag(), Some("My key"));
+///
+/// // After `change_hostname_for_access_keys("vpn.example.com")`
+/// let url = url.with_host("vpn.example.com");
+/// assert_eq!(
+///     url.to_string(),
+///     "ss://Y2hhY2hhMjAtaWV0Zi1wb2x5MTMwNTpzZWNyZXQ=@vpn.example.com:12345/?outline=1#My%20key"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowsocksUrl {
+    method: String,
+    password: String,
+    host: String,
+    port: u16,
+    query: Vec<(String, String)>,
+    tag: Option<String>,
+    user_info: UserInfo,
+}
+
+/// Errors returned when parsing a `ShadowsocksUrl`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ShadowsocksUrlError {
+    /// The URL doesn't start with `ss://`.
+    InvalidScheme,
+    /// The URL has no `userinfo@` part.
+    MissingUserInfo,
+    /// The userinfo is neither valid base64 nor `method:password`.
+    InvalidUserInfo,
+    /// The host is empty or malformed.
+    InvalidHost,
+    /// The port is missing or not an integer from 1 through 65535.
+    InvalidPort,
+}
+
+impl fmt::Display for ShadowsocksUrlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ShadowsocksUrlError::InvalidScheme => "The URL doesn't start with `ss://`.",
+            ShadowsocksUrlError::MissingUserInfo => "The URL has no method and password.",
+            ShadowsocksUrlError::InvalidUserInfo => {
+                "The method and password are neither base64 nor `method:password`."
+            }
+            ShadowsocksUrlError::InvalidHost => "The host is empty or malformed.",
+            ShadowsocksUrlError::InvalidPort => {
+                "The port is missing or not an integer from 1 through 65535."
+            }
+        })
+    }
+}
+
+impl std::error::Error for ShadowsocksUrlError {}
+
+impl ShadowsocksUrl {
+    /// Creates a URL without query parameters or tag. The userinfo is written as URL-safe base64
+    /// without padding, as recommended by SIP002.
+    ///
+    /// # Errors
+    ///
+    /// - `ShadowsocksUrlError::InvalidPort`: If `port` is not an integer from 1 through 65535.
+    pub fn new(
+        method: impl Into<String>,
+        password: impl Into<String>,
+        host: impl Into<String>,
+        port: impl TryInto<NonZeroU16>,
+    ) -> Result<Self, ShadowsocksUrlError> {
+        Ok(ShadowsocksUrl {
+            method: method.into(),
+            password: password.into(),
+            host: unbracket(host.into()),
+            port: validate_port(port)?,
+            query: Vec::new(),
+            tag: None,
+            user_info: UserInfo::Base64Url,
+        })
+    }
+
+    /// Returns the encryption method, e.g. `chacha20-ietf-poly1305`.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Returns the password.
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+
+    /// Returns the hostname or IP address, without brackets for IPv6 addresses.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Returns the port.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Returns the decoded `#tag`, usually the key name.
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    /// Returns the decoded query parameters, e.g. `("outline", "1")` or `("plugin", ...)`.
+    pub fn query(&self) -> &[(String, String)] {
+        &self.query
+    }
+
+    /// Returns the value of the first query parameter named `name`.
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns `true` for URLs generated by an Outline server (with `outline=1`).
+    pub fn is_outline(&self) -> bool {
+        self.query_param("outline") == Some("1")
+    }
+
+    /// Replaces the host, e.g. after `change_hostname_for_access_keys`. IPv6 addresses may be
+    /// written with or without brackets.
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = unbracket(host.into());
+        self
+    }
+
+    /// Replaces the port.
+    ///
+    /// # Errors
+    ///
+    /// - `ShadowsocksUrlError::InvalidPort`: If `port` is not an integer from 1 through 65535.
+    pub fn with_port(
+        mut self,
+        port: impl TryInto<NonZeroU16>,
+    ) -> Result<Self, ShadowsocksUrlError> {
+        self.port = validate_port(port)?;
+        Ok(self)
+    }
+
+    /// Sets or removes the `#tag`.
+    pub fn with_tag(mut self, tag: Option<String>) -> Self {
+        self.tag = tag;
+        self
+    }
+
+    /// Appends a query parameter.
+    pub fn with_query_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+}
+
+impl FromStr for ShadowsocksUrl {
+    type Err = ShadowsocksUrlError;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        let rest = url
+            .strip_prefix("ss://")
+            .ok_or(ShadowsocksUrlError::InvalidScheme)?;
+        let (rest, tag) = match rest.split_once('#') {
+            Some((rest, tag)) => (rest, Some(decode(tag))),
+            None => (rest, None),
+        };
+        let (rest, query) = match rest.split_once('?') {
+            Some((rest, query)) => (rest, parse_query(query)),
+            None => (rest, Vec::new()),
+        };
+        let rest = rest.strip_suffix('/').unwrap_or(rest);
+        let (user_info, address) = rest
+            .rsplit_once('@')
+            .ok_or(ShadowsocksUrlError::MissingUserInfo)?;
+        let (method, password, user_info) = parse_user_info(user_info)?;
+        let (host, port) = parse_address(address)?;
+
+        Ok(ShadowsocksUrl {
+            method,
+            password,
+            host,
+            port,
+            query,
+            tag,
+            user_info,
+        })
+    }
+}
+
+impl fmt::Display for ShadowsocksUrl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let credentials = format!("{}:{}", self.method, self.password);
+        let user_info = match self.user_info {
+            UserInfo::Base64 => STANDARD.encode(credentials),
+            UserInfo::Base64Url => URL_SAFE_NO_PAD.encode(credentials),
+            UserInfo::Plain => format!(
+                "{}:{}",
+                utf8_percent_encode(&self.method, USERINFO),
+                utf8_percent_encode(&self.password, USERINFO)
+            ),
+        };
+        write!(f, "ss://{}@", user_info)?;
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)?;
+        } else {
+            write!(f, "{}:{}", self.host, self.port)?;
+        }
+        for (index, (name, value)) in self.query.iter().enumerate() {
+            write!(
+                f,
+                "{}{}={}",
+                if index == 0 { "/?" } else { "&" },
+                utf8_percent_encode(name, QUERY),
+                utf8_percent_encode(value, QUERY)
+            )?;
+        }
+        if let Some(tag) = &self.tag {
+            write!(f, "#{}", utf8_percent_encode(tag, FRAGMENT))?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for ShadowsocksUrl {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ShadowsocksUrl {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let url = String::deserialize(deserializer)?;
+        url.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+fn validate_port(port: impl TryInto<NonZeroU16>) -> Result<u16, ShadowsocksUrlError> {
+    port.try_into()
+        .map(NonZeroU16::get)
+        .map_err(|_| ShadowsocksUrlError::InvalidPort)
+}
+
+/// Removes the brackets around an IPv6 address, which `Display` adds back.
+fn unbracket(host: String) -> String {
+    match host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+    {
+        Some(address) => address.to_string(),
+        None => host,
+    }
+}
+
+fn decode(value: &str) -> String {
+    percent_decode_str(value).decode_utf8_lossy().into_owned()
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((name, value)) => (decode(name), decode(value)),
+            None => (decode(pair), String::new()),
+        })
+        .collect()
+}
+
+fn parse_user_info(user_info: &str) -> Result<(String, String, UserInfo), ShadowsocksUrlError> {
+    // A base64 userinfo never contains ':', the plain form always does
+    if let Some((method, password)) = user_info.split_once(':') {
+        return Ok((decode(method), decode(password), UserInfo::Plain));
+    }
+
+    let user_info = decode(user_info);
+    let (engine, form) = if user_info.contains(['-', '_']) {
+        (LENIENT_URL_SAFE, UserInfo::Base64Url)
+    } else if user_info.contains(['+', '/', '=']) || user_info.len() % 4 == 0 {
+        (LENIENT_STANDARD, UserInfo::Base64)
+    } else {
+        (LENIENT_URL_SAFE, UserInfo::Base64Url)
+    };
+    let credentials = engine
+        .decode(&user_info)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or(ShadowsocksUrlError::InvalidUserInfo)?;
+    let (method, password) = credentials
+        .split_once(':')
+        .ok_or(ShadowsocksUrlError::InvalidUserInfo)?;
+    if method.is_empty() {
+        return Err(ShadowsocksUrlError::InvalidUserInfo);
+    }
+
+    Ok((method.to_string(), password.to_string(), form))
+}
+
+fn parse_address(address: &str) -> Result<(String, u16), ShadowsocksUrlError> {
+    let (host, port) = if let Some(rest) = address.strip_prefix('[') {
+        let (host, rest) = rest
+            .split_once(']')
+            .ok_or(ShadowsocksUrlError::InvalidHost)?;
+        let port = rest
+            .strip_prefix(':')
+            .ok_or(ShadowsocksUrlError::InvalidPort)?;
+        (host, port)
+    } else {
+        address
+            .rsplit_once(':')
+            .ok_or(ShadowsocksUrlError::InvalidPort)?
+    };
+    if host.is_empty() || host.contains(['/', '@', '[', ']']) {
+        return Err(ShadowsocksUrlError::InvalidHost);
+    }
+    let port = port
+        .parse::<u16>()
+        .ok()
+        .filter(|port| *port != 0)
+        .ok_or(ShadowsocksUrlError::InvalidPort)?;
+
+    Ok((host.to_string(), port))
+}
@@ -0,0 +1,109 @@
+//! Certificate pinning for the self-signed certificate of the Outline VPN server.
+//!
+//! The Outline installer prints the SHA-256 fingerprint of the server certificate next to the API URL.
+//! Instead of validating the certificate chain, the TLS handshake checks that the leaf certificate
+//! hashes to that fingerprint and fails otherwise.
+
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, CertificateError, ClientConfig, ServerName};
+use sha2::{Digest, Sha256};
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Normalizes a certificate fingerprint to lowercase hex without separators.
+pub(crate) fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(|c| *c != ':')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Returns the SHA-256 fingerprint of a DER-encoded certificate as lowercase hex.
+pub(crate) fn fingerprint_of(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Builds a rustls configuration that only accepts a server certificate matching `fingerprint`.
+pub(crate) fn pinned_client_config(fingerprint: &str) -> ClientConfig {
+    ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(PinnedCertificateVerifier {
+            fingerprint: normalize_fingerprint(fingerprint),
+        }))
+        .with_no_client_auth()
+}
+
+/// Raised during the handshake when the server certificate doesn't match the pinned fingerprint.
+#[derive(Debug)]
+pub(crate) struct CertificatePinMismatch {
+    pub(crate) expected: String,
+    pub(crate) actual: String,
+}
+
+impl fmt::Display for CertificatePinMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "certificate SHA-256 {} doesn't match the pinned {}",
+            self.actual, self.expected
+        )
+    }
+}
+
+impl StdError for CertificatePinMismatch {}
+
+/// Walks the source chain of a request error looking for a pinning failure.
+///
+/// `std::io::Error` doesn't expose the error it wraps through `source()`, so wrapped errors are
+/// unpacked with `get_ref()` on the way.
+pub(crate) fn find_pin_mismatch(error: &reqwest::Error) -> Option<&CertificatePinMismatch> {
+    let mut current: Option<&(dyn StdError + 'static)> = Some(error);
+    while let Some(error) = current {
+        if let Some(rustls::Error::InvalidCertificate(CertificateError::Other(other))) =
+            error.downcast_ref::<rustls::Error>()
+        {
+            return other.downcast_ref::<CertificatePinMismatch>();
+        }
+        current = match error.downcast_ref::<std::io::Error>() {
+            Some(io_error) => io_error
+                .get_ref()
+                .map(|inner| inner as &(dyn StdError + 'static)),
+            None => error.source(),
+        };
+    }
+    None
+}
+
+struct PinnedCertificateVerifier {
+    fingerprint: String,
+}
+
+impl ServerCertVerifier for PinnedCertificateVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let actual = fingerprint_of(&end_entity.0);
+        if actual == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                Arc::new(CertificatePinMismatch {
+                    expected: self.fingerprint.clone(),
+                    actual,
+                }),
+            )))
+        }
+    }
+}
@@ -0,0 +1,242 @@
+#![cfg(any(feature = "blocking", feature = "async"))]
+
+mod common;
+
+use common::{spawn_api_stub, ApiStub, CapturedRequest};
+use outline_api::CreateAccessKeyRequest;
+use serde_json::json;
+use std::time::Duration;
+
+const KEY: &str = r#"{"id":"my key/1","name":"Alice","password":"Xx","port":18162,"method":"chacha20-ietf-poly1305","accessUrl":"ss://Y2hhY2hhMjAtaWV0Zi1wb2x5MTMwNTpYeFh4WHg=@203.0.113.7:18162/?outline=1"}"#;
+
+/// Answers `PUT /access-keys/{id}` with the created key, or `409` once the key exists.
+fn create_stub() -> ApiStub {
+    let mut created = false;
+    spawn_api_stub(
+        move |request| match (request.method.as_str(), request.path.as_str()) {
+            ("PUT", "/secret/access-keys/my%20key%2F1") if !created => {
+                created = true;
+                (201, KEY.to_string())
+            }
+            ("PUT", "/secret/access-keys/my%20key%2F1")
+            | ("PUT", "/secret/server/port-for-new-access-keys") => (
+                409,
+                r#"{"code":"Conflict","message":"Conflict"}"#.to_string(),
+            ),
+            _ => (
+                404,
+                r#"{"code":"ResourceNotFound","message":"Not found"}"#.to_string(),
+            ),
+        },
+    )
+}
+
+fn request() -> CreateAccessKeyRequest {
+    CreateAccessKeyRequest {
+        name: Some("Alice".to_string()),
+        ..Default::default()
+    }
+}
+
+fn assert_put_with_body(request: CapturedRequest) {
+    assert_eq!(request.method, "PUT");
+    assert_eq!(request.path, "/secret/access-keys/my%20key%2F1");
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&request.body).unwrap(),
+        json!({ "name": "Alice" })
+    );
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn create_access_key_with_id_puts_to_encoded_path() {
+    use outline_api::{APIError, OutlineVPN};
+
+    let stub = create_stub();
+    let vpn = OutlineVPN::builder()
+        .api_url(stub.api_url)
+        .cert_sha256(stub.cert_sha256)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    let key = vpn
+        .create_access_key_with_id("my key/1", &request())
+        .unwrap();
+    assert_eq!(key.id.as_str(), "my key/1");
+    assert_put_with_body(stub.requests.recv().unwrap());
+
+    assert!(matches!(
+        vpn.create_access_key_with_id("my key/1", &request()),
+        Err(APIError::AccessKeyAlreadyExists(_))
+    ));
+    assert!(matches!(
+        vpn.change_default_port_for_newly_created_access(8080),
+        Err(APIError::PortConflict(_))
+    ));
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_create_access_key_with_id_puts_to_encoded_path() {
+    use outline_api::asynchronous::OutlineVPN;
+    use outline_api::APIError;
+
+    let stub = create_stub();
+    let vpn = OutlineVPN::builder()
+        .api_url(stub.api_url)
+        .cert_sha256(stub.cert_sha256)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    let key = vpn
+        .create_access_key_with_id("my key/1", &request())
+        .await
+        .unwrap();
+    assert_eq!(key.id.as_str(), "my key/1");
+    assert_put_with_body(stub.requests.recv().unwrap());
+
+    assert!(matches!(
+        vpn.create_access_key_with_id("my key/1", &request()).await,
+        Err(APIError::AccessKeyAlreadyExists(_))
+    ));
+    assert!(matches!(
+        vpn.change_default_port_for_newly_created_access(8080).await,
+        Err(APIError::PortConflict(_))
+    ));
+}
+
+/// Answers `GET /access-keys/{id}` like a server without that route, then lists the keys.
+fn legacy_stub() -> ApiStub {
+    spawn_api_stub(
+        |request| match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/secret/access-keys") => (200, format!(r#"{{"accessKeys":[{}]}}"#, KEY)),
+            _ => (
+                404,
+                r#"{"code":"ResourceNotFound","message":"/secret/access-keys/1 does not exist"}"#
+                    .to_string(),
+            ),
+        },
+    )
+}
+
+/// Answers `GET /access-keys/{id}` like a server with that route that doesn't have the key.
+fn missing_key_stub() -> ApiStub {
+    spawn_api_stub(|_| {
+        (
+            404,
+            r#"{"code":"NotFound","message":"Access key \"1\" not found"}"#.to_string(),
+        )
+    })
+}
+
+fn paths(stub: &ApiStub) -> Vec<String> {
+    stub.requests
+        .try_iter()
+        .map(|request| format!("{} {}", request.method, request.path))
+        .collect()
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn get_access_key_by_id_falls_back_to_list_on_resource_not_found() {
+    use outline_api::{APIError, OutlineVPN};
+
+    let stub = legacy_stub();
+    let vpn = OutlineVPN::builder()
+        .api_url(&stub.api_url)
+        .cert_sha256(&stub.cert_sha256)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    assert_eq!(vpn.get_access_key_by_id("my key/1").unwrap().name, "Alice");
+    assert!(matches!(
+        vpn.get_access_key_by_id("2"),
+        Err(APIError::AccessKeyInexistent(_))
+    ));
+    assert_eq!(
+        paths(&stub),
+        [
+            "GET /secret/access-keys/my%20key%2F1",
+            "GET /secret/access-keys",
+            "GET /secret/access-keys/2",
+            "GET /secret/access-keys",
+        ]
+    );
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn get_access_key_by_id_reports_missing_key_without_fallback() {
+    use outline_api::{APIError, OutlineVPN};
+
+    let stub = missing_key_stub();
+    let vpn = OutlineVPN::builder()
+        .api_url(&stub.api_url)
+        .cert_sha256(&stub.cert_sha256)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    assert!(matches!(
+        vpn.get_access_key_by_id("1"),
+        Err(APIError::AccessKeyInexistent(_))
+    ));
+    assert_eq!(paths(&stub), ["GET /secret/access-keys/1"]);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_get_access_key_by_id_falls_back_to_list_on_resource_not_found() {
+    use outline_api::asynchronous::OutlineVPN;
+    use outline_api::APIError;
+
+    let stub = legacy_stub();
+    let vpn = OutlineVPN::builder()
+        .api_url(&stub.api_url)
+        .cert_sha256(&stub.cert_sha256)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        vpn.get_access_key_by_id("my key/1").await.unwrap().name,
+        "Alice"
+    );
+    assert!(matches!(
+        vpn.get_access_key_by_id("2").await,
+        Err(APIError::AccessKeyInexistent(_))
+    ));
+    assert_eq!(
+        paths(&stub),
+        [
+            "GET /secret/access-keys/my%20key%2F1",
+            "GET /secret/access-keys",
+            "GET /secret/access-keys/2",
+            "GET /secret/access-keys",
+        ]
+    );
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_get_access_key_by_id_reports_missing_key_without_fallback() {
+    use outline_api::asynchronous::OutlineVPN;
+    use outline_api::APIError;
+
+    let stub = missing_key_stub();
+    let vpn = OutlineVPN::builder()
+        .api_url(&stub.api_url)
+        .cert_sha256(&stub.cert_sha256)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    assert!(matches!(
+        vpn.get_access_key_by_id("1").await,
+        Err(APIError::AccessKeyInexistent(_))
+    ));
+    assert_eq!(paths(&stub), ["GET /secret/access-keys/1"]);
+}
@@ -0,0 +1,223 @@
+mod common;
+
+use common::access_key;
+use outline_api::{
+    AccessKeyId, ByteSize, DataLimit, ExtraFields, Server, SinkError, TransferMetrics, UsageAlert,
+    UsageWatcher,
+};
+use std::collections::HashMap;
+
+fn server(limit: Option<u64>) -> Server {
+    let mut server: Server = serde_json::from_str(common::SERVER_INFO).unwrap();
+    server.access_key_data_limit = limit.map(DataLimit::new);
+    server
+}
+
+fn metrics(bytes: &[(&str, u64)]) -> TransferMetrics {
+    TransferMetrics {
+        bytes_transferred_by_user_id: bytes
+            .iter()
+            .map(|(id, bytes)| (AccessKeyId::from(*id), *bytes))
+            .collect::<HashMap<_, _>>(),
+        extra: ExtraFields::new(),
+    }
+}
+
+fn thresholds(alerts: &[UsageAlert]) -> Vec<(&str, u8)> {
+    alerts
+        .iter()
+        .map(|alert| (alert.key_id.as_str(), alert.threshold))
+        .collect()
+}
+
+#[test]
+fn reports_each_threshold_once() {
+    let mut watcher = UsageWatcher::new();
+    let server = server(None);
+    let keys = [access_key("0", "Alice", Some(1000))];
+
+    assert!(watcher
+        .check(&server, &keys, &metrics(&[("0", 799)]))
+        .is_empty());
+
+    let alerts = watcher.check(&server, &keys, &metrics(&[("0", 850)]));
+    assert_eq!(
+        alerts,
+        [UsageAlert {
+            server_name: "My Server".to_string(),
+            key_id: AccessKeyId::from("0"),
+            key_name: "Alice".to_string(),
+            bytes_transferred: ByteSize::new(850),
+            limit: ByteSize::KB,
+            threshold: 80,
+        }]
+    );
+    assert_eq!(alerts[0].utilization(), 0.85);
+    assert_eq!(
+        alerts[0].to_string(),
+        r#"Key "0" (Alice) on My Server reached 80% of its data limit: 850 B of 1 KB"#
+    );
+
+    assert!(watcher
+        .check(&server, &keys, &metrics(&[("0", 900)]))
+        .is_empty());
+    let alerts = watcher.check(&server, &keys, &metrics(&[("0", 1000)]));
+    assert_eq!(thresholds(&alerts), [("0", 100)]);
+    assert!(watcher
+        .check(&server, &keys, &metrics(&[("0", 1200)]))
+        .is_empty());
+}
+
+#[test]
+fn rearms_when_usage_drops() {
+    let mut watcher = UsageWatcher::new().thresholds([50, 90]);
+    let server = server(None);
+
+    let alerts = watcher.check(
+        &server,
+        &[access_key("0", "", Some(100))],
+        &metrics(&[("0", 95)]),
+    );
+    // Only the highest crossed threshold is reported
+    assert_eq!(thresholds(&alerts), [("0", 90)]);
+
+    // The limit was raised
+    let keys = [access_key("0", "", Some(1000))];
+    assert!(watcher
+        .check(&server, &keys, &metrics(&[("0", 95)]))
+        .is_empty());
+    let alerts = watcher.check(&server, &keys, &metrics(&[("0", 500)]));
+    assert_eq!(thresholds(&alerts), [("0", 50)]);
+}
+
+#[test]
+fn falls_back_to_the_server_limit() {
+    let mut watcher = UsageWatcher::new();
+    let keys = [
+        access_key("0", "", None),
+        access_key("1", "", Some(10_000)),
+        access_key("2", "", None),
+    ];
+    let usage = metrics(&[("0", 900), ("1", 900)]);
+
+    assert!(watcher.check(&server(None), &keys, &usage).is_empty());
+    let alerts = watcher.check(&server(Some(1000)), &keys, &usage);
+    assert_eq!(thresholds(&alerts), [("0", 80)]);
+}
+
+#[test]
+fn tells_keys_of_different_servers_apart() {
+    let mut watcher = UsageWatcher::new();
+    let eu = server(None);
+    let mut us = server(None);
+    us.name = "US".to_string();
+    let keys = [access_key("0", "", Some(1000))];
+
+    let alerts = watcher.check(&eu, &keys, &metrics(&[("0", 850)]));
+    assert_eq!(thresholds(&alerts), [("0", 80)]);
+    // Key 0 of another server is a different key
+    let alerts = watcher.check(&us, &keys, &metrics(&[("0", 900)]));
+    assert_eq!(thresholds(&alerts), [("0", 80)]);
+    // Checking one server keeps what was reported for the other
+    assert!(watcher
+        .check(&eu, &keys, &metrics(&[("0", 850)]))
+        .is_empty());
+    assert!(watcher
+        .check(&us, &keys, &metrics(&[("0", 900)]))
+        .is_empty());
+}
+
+#[test]
+fn retries_alerts_that_failed_to_send() {
+    let mut watcher = UsageWatcher::new();
+    let server = server(None);
+    let keys = [
+        access_key("0", "", Some(1000)),
+        access_key("1", "", Some(1000)),
+    ];
+    let usage = metrics(&[("0", 800), ("1", 800)]);
+
+    let down = |alert: &UsageAlert| -> Result<(), SinkError> {
+        if alert.key_id == "1" {
+            Err("webhook is down".into())
+        } else {
+            Ok(())
+        }
+    };
+    let failures = watcher.check_and_send(&server, &keys, &usage, &down);
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].0.key_id, "1");
+
+    let sent = std::sync::Mutex::new(Vec::new());
+    let up = |alert: &UsageAlert| -> Result<(), SinkError> {
+        sent.lock().unwrap().push(alert.clone());
+        Ok(())
+    };
+    assert!(watcher
+        .check_and_send(&server, &keys, &usage, &up)
+        .is_empty());
+    assert_eq!(thresholds(&sent.lock().unwrap()), [("1", 80)]);
+    assert!(watcher.check(&server, &keys, &usage).is_empty());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn polls_a_server() {
+    use outline_api::AlertSink;
+
+    let mock = outline_api::mock::MockServer::start();
+    let vpn = mock.client();
+    let key = vpn.create_access_key().unwrap();
+    vpn.set_data_transfer_limit_by_id(&key.id, 2000).unwrap();
+    mock.set_bytes_transferred(key.id.clone(), 2000);
+
+    let mut watcher = UsageWatcher::new();
+    let alerts = watcher.poll(&vpn).unwrap();
+    assert_eq!(thresholds(&alerts), [("0", 100)]);
+
+    let sent = std::sync::Mutex::new(Vec::new());
+    let sink = |alert: &UsageAlert| -> Result<(), SinkError> {
+        sent.lock().unwrap().push(alert.clone());
+        Ok(())
+    };
+    sink.send(&alerts[0]).unwrap();
+    assert_eq!(*sent.lock().unwrap(), alerts);
+}
+
+#[cfg(feature = "webhook")]
+#[test]
+fn webhook_posts_alerts_as_json() {
+    use outline_api::{AlertSink, WebhookSink};
+
+    let alert = UsageAlert {
+        server_name: "My Server".to_string(),
+        key_id: AccessKeyId::from("7"),
+        key_name: "Bob".to_string(),
+        bytes_transferred: ByteSize::new(900),
+        limit: ByteSize::KB,
+        threshold: 80,
+    };
+
+    let (url, requests) = common::spawn_http_capture();
+    WebhookSink::new(&url).unwrap().send(&alert).unwrap();
+    let request = requests.recv().unwrap();
+    assert_eq!(
+        (request.method.as_str(), request.path.as_str()),
+        ("POST", "/secret")
+    );
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&request.body).unwrap(),
+        serde_json::json!({
+            "serverName": "My Server",
+            "keyId": "7",
+            "keyName": "Bob",
+            "bytesTransferred": 900,
+            "limit": 1000,
+            "threshold": 80,
+        })
+    );
+
+    let (url, _requests) = common::spawn_http_stub(|_| (500, String::new()));
+    assert!(WebhookSink::new(&url).unwrap().send(&alert).is_err());
+    assert!(WebhookSink::new("not a url").is_err());
+}
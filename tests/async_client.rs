@@ -0,0 +1,58 @@
+#![cfg(feature = "async")]
+
+mod common;
+
+use common::spawn_tls_stub;
+use outline_api::asynchronous::OutlineVPN;
+use outline_api::APIError;
+use std::time::Duration;
+
+#[tokio::test]
+async fn get_server_info() {
+    let (api_url, fingerprint) = spawn_tls_stub();
+    let vpn = OutlineVPN::builder()
+        .api_url(&api_url)
+        .cert_sha256(&fingerprint)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    let server = vpn.get_server_info().await.unwrap();
+
+    assert_eq!(server.name, "My Server");
+    assert_eq!(server.port_for_new_access_keys, 1234);
+}
+
+#[tokio::test]
+async fn rejects_certificate_not_matching_fingerprint() {
+    let (api_url, _) = spawn_tls_stub();
+    let pinned = "0".repeat(64);
+    let vpn = OutlineVPN::builder()
+        .api_url(&api_url)
+        .cert_sha256(&pinned)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    let result = vpn.get_server_info().await;
+
+    assert!(matches!(
+        result,
+        Err(APIError::CertificatePinMismatch { .. })
+    ));
+}
+
+#[tokio::test]
+async fn access_keys_against_mock_server() {
+    let server = outline_api::mock::MockServer::start();
+    let vpn = server.async_client();
+
+    let key = vpn.create_access_key().await.unwrap();
+    vpn.change_name_for_access_key(&key.id, "Alice")
+        .await
+        .unwrap();
+
+    let key = vpn.get_access_key_by_id(&key.id).await.unwrap();
+    assert_eq!(key.name, "Alice");
+    assert_eq!(vpn.list_access_keys().await.unwrap().access_keys, [key]);
+}
@@ -0,0 +1,200 @@
+#![cfg(feature = "blocking")]
+
+use outline_api::{APIError, OutlineVPN};
+use std::time::Duration;
+
+const API_URL: &str = "https://127.0.0.1:1234/secret";
+const CERT_SHA256: &str = "E2DE8E9A0A5BD9E8B3A8A5A9D5C8F8E2DE8E9A0A5BD9E8B3A8A5A9D5C8F82A75";
+
+fn invalid_field(result: Result<OutlineVPN, APIError>) -> &'static str {
+    match result {
+        Err(APIError::InvalidConfig { field, .. }) => field,
+        Err(err) => panic!("expected an invalid config, got {:?}", err),
+        Ok(_) => panic!("expected an invalid config, got a client"),
+    }
+}
+
+#[test]
+fn builds_with_valid_settings() {
+    let result = OutlineVPN::builder()
+        .api_url(API_URL)
+        .cert_sha256(CERT_SHA256)
+        .timeout(Duration::from_secs(5))
+        .connect_timeout(Duration::from_secs(2))
+        .user_agent("outline-api-tests")
+        .build();
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn accepts_colon_separated_fingerprint() {
+    let fingerprint = CERT_SHA256
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| std::str::from_utf8(pair).unwrap())
+        .collect::<Vec<_>>()
+        .join(":");
+
+    let result = OutlineVPN::builder()
+        .api_url(API_URL)
+        .cert_sha256(&fingerprint)
+        .build();
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn rejects_malformed_fingerprints() {
+    for fingerprint in [
+        "",
+        "cert_sha256_hash",
+        &CERT_SHA256[1..],
+        &CERT_SHA256.replace('E', "G"),
+        "E2:DE8E9A0A5BD9E8B3A8A5A9D5C8F8E2DE8E9A0A5BD9E8B3A8A5A9D5C8F82A75",
+        "E2DE8E9A0A5BD9E8B3A8A5A9D5C8F8E2DE8E9A0A5BD9E8B3A8A5A9D5C8F82A75\n",
+    ] {
+        let result = OutlineVPN::builder()
+            .api_url(API_URL)
+            .cert_sha256(fingerprint)
+            .build();
+
+        assert_eq!(invalid_field(result), "cert_sha256", "{:?}", fingerprint);
+    }
+}
+
+#[test]
+fn rejects_malformed_api_urls() {
+    for api_url in [
+        "",
+        "127.0.0.1:1234/secret",
+        "ftp://127.0.0.1/secret",
+        "http://127.0.0.1:1234/secret",
+    ] {
+        let result = OutlineVPN::builder()
+            .api_url(api_url)
+            .cert_sha256(CERT_SHA256)
+            .build();
+
+        assert_eq!(invalid_field(result), "api_url", "{:?}", api_url);
+    }
+}
+
+#[test]
+fn requires_api_url_and_fingerprint() {
+    let without_url = OutlineVPN::builder().cert_sha256(CERT_SHA256).build();
+    let without_fingerprint = OutlineVPN::builder().api_url(API_URL).build();
+
+    assert_eq!(invalid_field(without_url), "api_url");
+    assert_eq!(invalid_field(without_fingerprint), "cert_sha256");
+}
+
+#[test]
+fn reads_installer_access_config() {
+    let access_config = format!(
+        r#"{{"apiUrl":"{}","certSha256":"{}"}}"#,
+        API_URL, CERT_SHA256
+    );
+
+    let result = OutlineVPN::builder()
+        .access_config(&access_config)
+        .unwrap()
+        .build();
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn rejects_malformed_installer_access_config() {
+    let result = OutlineVPN::builder().access_config(r#"{"apiUrl":"https://127.0.0.1"}"#);
+
+    assert!(matches!(
+        result,
+        Err(APIError::InvalidConfig {
+            field: "access_config",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn accepts_pre_built_client_without_fingerprint() {
+    let result = OutlineVPN::builder()
+        .api_url(API_URL)
+        .client(reqwest::blocking::Client::new())
+        .build();
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn rejects_client_options_with_pre_built_client() {
+    let result = OutlineVPN::builder()
+        .api_url(API_URL)
+        .user_agent("outline-api-tests")
+        .client(reqwest::blocking::Client::new())
+        .build();
+
+    assert_eq!(invalid_field(result), "client");
+}
+
+#[test]
+fn rejects_fingerprint_with_pre_built_client() {
+    let result = OutlineVPN::builder()
+        .api_url(API_URL)
+        .cert_sha256(CERT_SHA256)
+        .client(reqwest::blocking::Client::new())
+        .build();
+    assert_eq!(invalid_field(result), "client");
+
+    // A pin from the installer access config would be ignored just the same
+    let access_config = format!(
+        r#"{{"apiUrl":"{}","certSha256":"{}"}}"#,
+        API_URL, CERT_SHA256
+    );
+    let result = OutlineVPN::builder()
+        .access_config(&access_config)
+        .unwrap()
+        .client(reqwest::blocking::Client::new())
+        .build();
+    assert_eq!(invalid_field(result), "client");
+}
+
+#[test]
+fn debug_output_redacts_secret() {
+    let vpn = OutlineVPN::builder()
+        .api_url(API_URL)
+        .cert_sha256(CERT_SHA256)
+        .build()
+        .unwrap();
+
+    let debug = format!("{:?} {:?}", vpn, vpn.clone());
+
+    assert!(debug.contains("https://127.0.0.1:1234/<redacted>"));
+    assert!(!debug.contains("secret"));
+}
+
+#[test]
+fn client_can_be_shared_across_threads() {
+    fn assert_send_sync<T: Send + Sync + Clone + 'static>() {}
+    assert_send_sync::<OutlineVPN>();
+
+    let clients: std::collections::HashMap<String, OutlineVPN> = ["a", "b"]
+        .into_iter()
+        .map(|name| {
+            let api_url = format!("https://{}.example.com:1234/secret", name);
+            let vpn = OutlineVPN::builder()
+                .api_url(api_url)
+                .cert_sha256(CERT_SHA256)
+                .build()
+                .unwrap();
+            (name.to_string(), vpn)
+        })
+        .collect();
+
+    std::thread::scope(|scope| {
+        for vpn in clients.values() {
+            scope.spawn(move || format!("{:?}", vpn));
+        }
+    });
+}
@@ -0,0 +1,120 @@
+use outline_api::{ByteSize, ByteSizeError, DataLimit};
+
+#[test]
+fn parses_decimal_and_binary_units() {
+    for (size, bytes) in [
+        ("0", 0),
+        ("1234", 1234),
+        ("1234 B", 1234),
+        ("500 MB", 500_000_000),
+        ("500mb", 500_000_000),
+        ("10GB", 10_000_000_000),
+        ("10GiB", 10 * 1024 * 1024 * 1024),
+        (" 2 TiB ", 2 << 40),
+        ("1.5 KiB", 1536),
+        ("0.3 KiB", 307),
+        ("1.000000000000000000001 TB", 1_000_000_000_000),
+        ("18446744073709551615", u64::MAX),
+    ] {
+        assert_eq!(
+            size.parse::<ByteSize>(),
+            Ok(ByteSize::new(bytes)),
+            "{}",
+            size
+        );
+    }
+}
+
+#[test]
+fn rejects_invalid_sizes_and_overflows() {
+    for (size, error) in [
+        ("", ByteSizeError::InvalidNumber),
+        ("GB", ByteSizeError::InvalidNumber),
+        ("-1 GB", ByteSizeError::InvalidNumber),
+        (".5 GB", ByteSizeError::InvalidNumber),
+        ("5. GB", ByteSizeError::InvalidNumber),
+        ("1.2.3 GB", ByteSizeError::InvalidNumber),
+        ("10 Gbit", ByteSizeError::UnknownUnit("Gbit".to_string())),
+        ("10 PB", ByteSizeError::UnknownUnit("PB".to_string())),
+        ("18446744073709551616", ByteSizeError::Overflow),
+        ("16777216 TiB", ByteSizeError::Overflow),
+        ("18446744073709551615.5 KB", ByteSizeError::Overflow),
+    ] {
+        assert_eq!(size.parse::<ByteSize>(), Err(error), "{}", size);
+    }
+}
+
+#[test]
+fn displays_the_largest_exact_unit() {
+    for (bytes, display) in [
+        (0, "0 B"),
+        (999, "999 B"),
+        (1000, "1 KB"),
+        (1024, "1 KiB"),
+        (1536, "1536 B"),
+        (1_500_000, "1500 KB"),
+        (10_000_000_000, "10 GB"),
+        (10 << 30, "10 GiB"),
+        (1 << 40, "1 TiB"),
+    ] {
+        let size = ByteSize::new(bytes);
+        assert_eq!(size.to_string(), display);
+        assert_eq!(display.parse::<ByteSize>(), Ok(size));
+    }
+}
+
+#[test]
+fn serializes_as_bytes_and_accepts_units() {
+    let limit = DataLimit::new(ByteSize::GIB);
+    assert_eq!(
+        serde_json::to_string(&limit).unwrap(),
+        r#"{"bytes":1073741824}"#
+    );
+    assert_eq!(
+        serde_json::from_str::<DataLimit>(r#"{"bytes":1073741824}"#).unwrap(),
+        limit
+    );
+    assert_eq!(
+        serde_json::from_str::<DataLimit>(r#"{"bytes":"1 GiB"}"#).unwrap(),
+        limit
+    );
+    assert!(serde_json::from_str::<DataLimit>(r#"{"bytes":-1}"#).is_err());
+    assert!(serde_json::from_str::<DataLimit>(r#"{"bytes":"1 GB/s"}"#).is_err());
+}
+
+#[test]
+fn constants_and_checked_arithmetic() {
+    assert_eq!(
+        ByteSize::GB.checked_mul(10),
+        Some(ByteSize::new(10_000_000_000))
+    );
+    assert_eq!(ByteSize::TIB.checked_mul(1 << 24), None);
+    assert_eq!(u64::from(ByteSize::MIB), 1 << 20);
+    assert_eq!(ByteSize::KB, 1000);
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+// Callers written for the former `&u64` parameters keep compiling
+#[allow(clippy::needless_borrows_for_generic_args)]
+fn limit_methods_accept_sizes_and_bytes() {
+    let server = outline_api::mock::MockServer::start();
+    let vpn = server.client();
+    vpn.create_access_key().unwrap();
+
+    vpn.set_data_transfer_limit_by_id("0", "10GiB".parse::<ByteSize>().unwrap())
+        .unwrap();
+    assert_eq!(
+        server.access_keys()[0].data_limit,
+        Some(DataLimit::new(10u64 << 30))
+    );
+
+    vpn.set_data_transfer_limit_for_all_access_keys(5000)
+        .unwrap();
+    vpn.set_data_transfer_limit_for_all_access_keys(&5000)
+        .unwrap();
+    assert_eq!(
+        server.server().access_key_data_limit,
+        Some(DataLimit::new(5000))
+    );
+}
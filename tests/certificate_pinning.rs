@@ -0,0 +1,67 @@
+#![cfg(feature = "blocking")]
+
+mod common;
+
+use common::spawn_tls_stub;
+use outline_api::{APIError, OutlineVPN};
+use std::time::Duration;
+
+#[test]
+fn accepts_certificate_matching_fingerprint() {
+    let (api_url, fingerprint) = spawn_tls_stub();
+    let vpn = OutlineVPN::builder()
+        .api_url(&api_url)
+        .cert_sha256(&fingerprint)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    let server = vpn.get_server_info().unwrap();
+
+    assert_eq!(server.name, "My Server");
+}
+
+#[test]
+fn accepts_colon_separated_lowercase_fingerprint() {
+    let (api_url, fingerprint) = spawn_tls_stub();
+    let fingerprint = fingerprint
+        .to_lowercase()
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| std::str::from_utf8(pair).unwrap())
+        .collect::<Vec<_>>()
+        .join(":");
+    let vpn = OutlineVPN::builder()
+        .api_url(&api_url)
+        .cert_sha256(&fingerprint)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    assert!(vpn.get_server_info().is_ok());
+}
+
+#[test]
+fn rejects_certificate_not_matching_fingerprint() {
+    let (api_url, fingerprint) = spawn_tls_stub();
+    let pinned = "0".repeat(64);
+    let vpn = OutlineVPN::builder()
+        .api_url(&api_url)
+        .cert_sha256(&pinned)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    match vpn.get_server_info() {
+        Err(APIError::CertificatePinMismatch {
+            endpoint,
+            expected,
+            actual,
+        }) => {
+            assert_eq!(endpoint, "/server");
+            assert_eq!(expected, pinned);
+            assert_eq!(actual, fingerprint.to_lowercase());
+        }
+        other => panic!("expected a pinning mismatch, got {:?}", other),
+    }
+}
@@ -0,0 +1,119 @@
+#![cfg(feature = "cli")]
+
+use outline_api::mock::MockServer;
+use std::process::{Command, Output};
+
+fn outline_ctl(server: &MockServer, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_outline-ctl"))
+        .args(args)
+        .env("OUTLINE_API_URL", server.api_url())
+        .env("OUTLINE_CERT_SHA256", server.cert_sha256())
+        .env_remove("OUTLINE_ACCESS_CONFIG")
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "outline-ctl failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn manages_keys() {
+    let server = MockServer::start();
+
+    let created = stdout(&outline_ctl(
+        &server,
+        &[
+            "keys", "create", "--name", "Alice", "--limit", "1000", "-o", "json",
+        ],
+    ));
+    let key: serde_json::Value = serde_json::from_str(&created).unwrap();
+    assert_eq!(key["name"], "Alice");
+    assert_eq!(key["dataLimit"]["bytes"], 1000);
+
+    stdout(&outline_ctl(&server, &["keys", "rename", "0", "Bob"]));
+    stdout(&outline_ctl(&server, &["keys", "remove-limit", "0"]));
+
+    let list = stdout(&outline_ctl(&server, &["keys", "list"]));
+    let mut lines = list.lines();
+    assert!(lines.next().unwrap().starts_with("ID  NAME  PORT"));
+    assert!(lines.next().unwrap().starts_with("0   Bob   12345"));
+
+    stdout(&outline_ctl(&server, &["keys", "delete", "0"]));
+    assert!(server.access_keys().is_empty());
+}
+
+#[test]
+fn reads_installer_access_config() {
+    let server = MockServer::builder().name("From config").start();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_outline-ctl"))
+        .args(["server", "info", "--output", "json"])
+        .env_remove("OUTLINE_API_URL")
+        .env_remove("OUTLINE_CERT_SHA256")
+        .env("OUTLINE_ACCESS_CONFIG", server.access_config())
+        .output()
+        .unwrap();
+
+    let info: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(info["name"], "From config");
+}
+
+#[test]
+fn reports_errors() {
+    let server = MockServer::start();
+
+    let output = outline_ctl(&server, &["keys", "delete", "42"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Access key inexistent"));
+}
+
+#[test]
+fn rejects_port_zero() {
+    let server = MockServer::start();
+
+    let output = outline_ctl(&server, &["server", "port", "0"]);
+
+    assert!(!output.status.success());
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn prints_qr_codes() {
+    let server = MockServer::start();
+    let key = server.client().create_access_key().unwrap();
+
+    let output = outline_ctl(&server, &["keys", "qr", "0", "--format", "png"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        output.stdout,
+        outline_api::QrCode::new(&key.access_url)
+            .unwrap()
+            .to_png(8)
+            .unwrap()
+    );
+
+    for format in ["svg", "png"] {
+        let output = outline_ctl(
+            &server,
+            &[
+                "keys",
+                "qr",
+                "0",
+                "--format",
+                format,
+                "--module-size",
+                "2000",
+            ],
+        );
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("module_size"));
+    }
+}
@@ -0,0 +1,231 @@
+// Each test crate uses only some of the helpers
+#![allow(dead_code)]
+
+use outline_api::{AccessKey, AccessKeyId, DataLimit, ExtraFields, Snapshot};
+use rustls::{Certificate, PrivateKey, ServerConfig, ServerConnection, StreamOwned};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const SERVER_INFO: &str = r#"{"name":"My Server","serverId":"40f1b4a3-5c82-45f4-80a6-a25cf36734d3","metricsEnabled":true,"createdTimestampMs":1536613192052,"version":"1.0.0","portForNewAccessKeys":1234,"hostnameForAccessKeys":"example.com"}"#;
+
+/// Builds an access key as returned by the server, with an `ss://` access URL on
+/// `203.0.113.7:18162`.
+pub fn access_key(id: &str, name: &str, limit: Option<u64>) -> AccessKey {
+    AccessKey {
+        id: AccessKeyId::from(id),
+        name: name.to_string(),
+        password: "XxXxXx".to_string(),
+        port: 18162,
+        method: "chacha20-ietf-poly1305".to_string(),
+        data_limit: limit.map(DataLimit::new),
+        access_url: "ss://Y2hhY2hhMjAtaWV0Zi1wb2x5MTMwNTpYeFh4WHg=@203.0.113.7:18162/?outline=1"
+            .to_string(),
+        extra: ExtraFields::new(),
+    }
+}
+
+/// Builds a snapshot of the bytes transferred by each key at `time`, without fingerprints.
+pub fn snapshot(time: SystemTime, keys: &[(&str, u64)]) -> Snapshot {
+    Snapshot {
+        timestamp_ms: time.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64,
+        bytes_transferred: keys
+            .iter()
+            .map(|(id, bytes)| (AccessKeyId::from(*id), *bytes))
+            .collect(),
+        key_fingerprints: Default::default(),
+        fingerprint_salt: String::new(),
+    }
+}
+
+/// Generates a self-signed certificate for `127.0.0.1`.
+///
+/// Returns the server TLS configuration and the SHA-256 fingerprint of the certificate.
+fn self_signed_config() -> (Arc<ServerConfig>, String) {
+    let certificate = rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()]).unwrap();
+    let der = certificate.serialize_der().unwrap();
+    let fingerprint = Sha256::digest(&der)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<String>();
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![Certificate(der)],
+            PrivateKey(certificate.serialize_private_key_der()),
+        )
+        .unwrap();
+    (Arc::new(config), fingerprint)
+}
+
+/// Serves a single `GET /server` response over TLS with a freshly generated self-signed certificate.
+///
+/// Returns the API URL and the SHA-256 fingerprint of the certificate.
+pub fn spawn_tls_stub() -> (String, String) {
+    let (config, fingerprint) = self_signed_config();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (socket, _) = listener.accept().unwrap();
+        let connection = ServerConnection::new(config).unwrap();
+        let mut stream = StreamOwned::new(connection, socket);
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            match stream.read(&mut buffer) {
+                Ok(0) | Err(_) => return,
+                Ok(read) => request.extend_from_slice(&buffer[..read]),
+            }
+        }
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            SERVER_INFO.len(),
+            SERVER_INFO
+        );
+        let _ = stream.write_all(response.as_bytes());
+        stream.conn.send_close_notify();
+        let _ = stream.flush();
+    });
+
+    (format!("https://{}/secret", address), fingerprint)
+}
+
+/// Captured HTTP request: method, path and body.
+#[derive(Debug)]
+pub struct CapturedRequest {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+/// Accepts plain HTTP requests, answers each with `204 No Content` and reports them on the channel.
+///
+/// Returns the URL and the receiving end of the channel. The API client only talks TLS, so this
+/// is for other endpoints such as webhooks; use `spawn_api_capture` for the API.
+pub fn spawn_http_capture() -> (String, Receiver<CapturedRequest>) {
+    spawn_http_stub(|_| (204, String::new()))
+}
+
+/// Accepts plain HTTP requests, answers each with the status and body returned by `respond` and
+/// reports them on the channel.
+///
+/// Returns the URL and the receiving end of the channel.
+pub fn spawn_http_stub<F>(mut respond: F) -> (String, Receiver<CapturedRequest>)
+where
+    F: FnMut(&CapturedRequest) -> (u16, String) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for socket in listener.incoming() {
+            let Ok(socket) = socket else { return };
+            if !answer(socket, &mut respond, &sender) {
+                return;
+            }
+        }
+    });
+
+    (format!("http://{}/secret", address), receiver)
+}
+
+/// API stub served over TLS with a freshly generated self-signed certificate.
+pub struct ApiStub {
+    pub api_url: String,
+    pub cert_sha256: String,
+    pub requests: Receiver<CapturedRequest>,
+}
+
+/// Accepts API requests over TLS, answers each with `204 No Content` and reports them on the
+/// channel.
+pub fn spawn_api_capture() -> ApiStub {
+    spawn_api_stub(|_| (204, String::new()))
+}
+
+/// Accepts API requests over TLS, answers each with the status and body returned by `respond`
+/// and reports them on the channel.
+pub fn spawn_api_stub<F>(mut respond: F) -> ApiStub
+where
+    F: FnMut(&CapturedRequest) -> (u16, String) + Send + 'static,
+{
+    let (config, cert_sha256) = self_signed_config();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let (sender, requests) = mpsc::channel();
+    thread::spawn(move || {
+        for socket in listener.incoming() {
+            let Ok(socket) = socket else { return };
+            let connection = ServerConnection::new(Arc::clone(&config)).unwrap();
+            let mut stream = StreamOwned::new(connection, socket);
+            let answered = answer(&mut stream, &mut respond, &sender);
+            stream.conn.send_close_notify();
+            let _ = stream.flush();
+            if !answered {
+                return;
+            }
+        }
+    });
+
+    ApiStub {
+        api_url: format!("https://{}/secret", address),
+        cert_sha256,
+        requests,
+    }
+}
+
+/// Reads one request from `stream`, answers it with `respond` and reports it to `sender`.
+///
+/// Returns `false` once nobody listens for requests anymore.
+fn answer<S, F>(mut stream: S, respond: &mut F, sender: &Sender<CapturedRequest>) -> bool
+where
+    S: Read + Write,
+    F: FnMut(&CapturedRequest) -> (u16, String),
+{
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let header_end = loop {
+        if let Some(position) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+        match stream.read(&mut buffer) {
+            Ok(0) | Err(_) => return true,
+            Ok(read) => request.extend_from_slice(&buffer[..read]),
+        }
+    };
+    let head = String::from_utf8_lossy(&request[..header_end]).to_string();
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .map(|(_, value)| value.trim().parse::<usize>().unwrap())
+        .unwrap_or(0);
+    while request.len() < header_end + content_length {
+        match stream.read(&mut buffer) {
+            Ok(0) | Err(_) => return true,
+            Ok(read) => request.extend_from_slice(&buffer[..read]),
+        }
+    }
+    let mut request_line = head.split_whitespace();
+    let captured = CapturedRequest {
+        method: request_line.next().unwrap_or_default().to_string(),
+        path: request_line.next().unwrap_or_default().to_string(),
+        body: String::from_utf8(request[header_end..header_end + content_length].to_vec()).unwrap(),
+    };
+    let (status, body) = respond(&captured);
+    let response = format!(
+        "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    // Reported before answering, so the request is on the channel once the client returns
+    let listening = sender.send(captured).is_ok();
+    let _ = stream.write_all(response.as_bytes());
+    let _ = stream.flush();
+    listening
+}
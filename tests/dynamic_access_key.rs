@@ -0,0 +1,68 @@
+mod common;
+
+use common::access_key;
+use outline_api::{DynamicAccessKey, DynamicAccessKeyError};
+
+#[test]
+fn document_from_access_key() {
+    let document = DynamicAccessKey::from_access_key(&access_key("0", "Alice", None)).unwrap();
+
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&document.to_json()).unwrap(),
+        serde_json::json!({
+            "server": "203.0.113.7",
+            "server_port": 18162,
+            "password": "XxXxXx",
+            "method": "chacha20-ietf-poly1305",
+        })
+    );
+}
+
+#[test]
+fn document_with_server_and_prefix() {
+    let document = DynamicAccessKey::from_access_key(&access_key("0", "Alice", None))
+        .unwrap()
+        .with_server("vpn.example.com")
+        .with_prefix("\u{16}\u{03}\u{01}\u{00}\u{a8}\u{01}\u{01}")
+        .unwrap();
+
+    let json = document.to_json();
+
+    assert!(json.contains(r#""server":"vpn.example.com""#));
+    assert!(json.contains(r#""prefix":"\u0016\u0003\u0001\u0000¨\u0001\u0001""#));
+    assert_eq!(
+        serde_json::from_str::<DynamicAccessKey>(&json).unwrap(),
+        document
+    );
+}
+
+#[test]
+fn rejects_prefix_beyond_one_byte_per_character() {
+    let document = DynamicAccessKey::from_access_key(&access_key("0", "Alice", None)).unwrap();
+
+    assert_eq!(
+        document.with_prefix("GET /\u{100}"),
+        Err(DynamicAccessKeyError::InvalidPrefix)
+    );
+}
+
+#[test]
+fn ssconf_urls() {
+    assert_eq!(
+        DynamicAccessKey::ssconf_url("https://keys.example.com/a/b.json?token=1", None).unwrap(),
+        "ssconf://keys.example.com/a/b.json?token=1"
+    );
+    assert_eq!(
+        DynamicAccessKey::ssconf_url("https://keys.example.com:8443/k#old", Some("Café key"))
+            .unwrap(),
+        "ssconf://keys.example.com:8443/k#Caf%C3%A9%20key"
+    );
+    assert_eq!(
+        DynamicAccessKey::ssconf_url("http://keys.example.com/k", None),
+        Err(DynamicAccessKeyError::InsecureConfigUrl)
+    );
+    assert_eq!(
+        DynamicAccessKey::ssconf_url("not a url", None),
+        Err(DynamicAccessKeyError::InvalidConfigUrl)
+    );
+}
@@ -0,0 +1,160 @@
+#![cfg(feature = "blocking")]
+
+use outline_api::mock::MockServer;
+use outline_api::{CreateAccessKeyRequest, DataLimit, Exporter, Fleet};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+fn get(address: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn exports_key_metrics() {
+    let server = MockServer::start();
+    let vpn = server.client();
+    vpn.create_access_key_with_options(&CreateAccessKeyRequest {
+        name: Some("Alice \"A\"".to_string()),
+        limit: Some(DataLimit::new(5000)),
+        ..Default::default()
+    })
+    .unwrap();
+    vpn.create_access_key().unwrap();
+    server.set_bytes_transferred("0", 1234);
+
+    let metrics = Exporter::new(Fleet::new().with_server("eu-1", vpn)).collect();
+
+    for expected in [
+        "# TYPE outline_key_bytes_transferred gauge",
+        "outline_scrape_success{server=\"eu-1\"} 1",
+        "outline_access_keys{server=\"eu-1\"} 2",
+        "outline_key_bytes_transferred{server=\"eu-1\",key_id=\"0\",key_name=\"Alice \\\"A\\\"\"} 1234",
+        "outline_key_bytes_transferred{server=\"eu-1\",key_id=\"1\",key_name=\"\"} 0",
+        "outline_key_data_limit_bytes{server=\"eu-1\",key_id=\"0\",key_name=\"Alice \\\"A\\\"\"} 5000",
+    ] {
+        assert!(
+            metrics.lines().any(|line| line == expected),
+            "missing {:?} in\n{}",
+            expected,
+            metrics
+        );
+    }
+    assert!(!metrics.contains("key_id=\"1\",key_name=\"\"} 5000"));
+}
+
+#[test]
+fn falls_back_to_server_wide_data_limit() {
+    let server = MockServer::start();
+    let vpn = server.client();
+    vpn.create_access_key_with_options(&CreateAccessKeyRequest {
+        limit: Some(DataLimit::new(5000)),
+        ..Default::default()
+    })
+    .unwrap();
+    vpn.create_access_key().unwrap();
+    vpn.set_data_transfer_limit_for_all_access_keys(9000)
+        .unwrap();
+
+    let metrics = Exporter::new(Fleet::new().with_server("eu-1", vpn)).collect();
+
+    assert!(metrics.contains(
+        "outline_key_data_limit_bytes{server=\"eu-1\",key_id=\"0\",key_name=\"\"} 5000\n"
+    ));
+    assert!(metrics.contains(
+        "outline_key_data_limit_bytes{server=\"eu-1\",key_id=\"1\",key_name=\"\"} 9000\n"
+    ));
+}
+
+#[test]
+fn failed_servers_only_report_scrape_failure() {
+    let healthy = MockServer::start();
+    let broken = MockServer::start();
+    broken.client().create_access_key().unwrap();
+    broken.fail_next_requests(1, 500);
+    let fleet = Fleet::new()
+        .with_server("broken", broken.client())
+        .with_server("healthy", healthy.client());
+
+    let metrics = Exporter::new(fleet).collect();
+
+    assert!(metrics.contains("outline_scrape_success{server=\"broken\"} 0\n"));
+    assert!(metrics.contains("outline_scrape_success{server=\"healthy\"} 1\n"));
+    assert!(metrics.contains("outline_access_keys{server=\"healthy\"} 0\n"));
+    assert!(!metrics.contains("outline_access_keys{server=\"broken\"}"));
+}
+
+#[test]
+fn serves_metrics_over_http() {
+    let server = MockServer::start();
+    server.client().create_access_key().unwrap();
+    let exporter = Exporter::new(Fleet::new().with_server("eu-1", server.client()))
+        .interval(Duration::from_millis(50))
+        .spawn("127.0.0.1:0")
+        .unwrap();
+
+    let response = get(exporter.local_addr(), "/metrics");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+    assert!(response
+        .contains("outline_key_bytes_transferred{server=\"eu-1\",key_id=\"0\",key_name=\"\"} 0\n"));
+
+    // Later polls pick up new values
+    server.set_bytes_transferred("0", 42);
+    let mut polled = false;
+    for _ in 0..100 {
+        if get(exporter.local_addr(), "/metrics").contains("key_name=\"\"} 42\n") {
+            polled = true;
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert!(polled);
+
+    assert!(get(exporter.local_addr(), "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
+
+#[test]
+fn rejects_zero_interval() {
+    let server = MockServer::start();
+    let result = Exporter::new(Fleet::new().with_server("eu-1", server.client()))
+        .interval(Duration::ZERO)
+        .spawn("127.0.0.1:0");
+
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[cfg(feature = "exporter")]
+#[test]
+fn binary_serves_configured_servers() {
+    let server = MockServer::builder().name("From config").start();
+    let address = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_outline-exporter"))
+        .arg("--server")
+        .arg(format!("eu-1={}", server.access_config()))
+        .args(["--listen", &address.to_string()])
+        .spawn()
+        .unwrap();
+
+    let mut response = None;
+    for _ in 0..100 {
+        if TcpStream::connect(address).is_ok() {
+            response = Some(get(address, "/metrics"));
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(response
+        .unwrap()
+        .contains("outline_scrape_success{server=\"eu-1\"} 1\n"));
+}
@@ -0,0 +1,67 @@
+#![cfg(feature = "blocking")]
+
+use outline_api::mock::MockServer;
+use outline_api::{APIError, DataLimit, Fleet, OutlineVPN};
+
+#[test]
+fn fans_out_to_every_server() {
+    let first = MockServer::start();
+    let second = MockServer::start();
+    first.client().create_access_key().unwrap();
+    let fleet = Fleet::new()
+        .with_server("first", first.client())
+        .with_server("second", second.client());
+
+    let keys = fleet.list_access_keys();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys["first"].as_ref().unwrap().access_keys.len(), 1);
+    assert!(keys["second"].as_ref().unwrap().access_keys.is_empty());
+
+    let results = fleet.set_data_transfer_limit_for_all_access_keys(1000);
+    assert!(results.values().all(Result::is_ok));
+    for server in [&first, &second] {
+        assert_eq!(
+            server.server().access_key_data_limit,
+            Some(DataLimit::new(1000))
+        );
+    }
+}
+
+#[test]
+fn reports_partial_failures() {
+    let healthy = MockServer::start();
+    let broken = MockServer::start();
+    let pinned_elsewhere = OutlineVPN::builder()
+        .api_url(broken.api_url())
+        .cert_sha256("0".repeat(64))
+        .build()
+        .unwrap();
+    let fleet: Fleet = [("healthy", healthy.client()), ("broken", pinned_elsewhere)]
+        .into_iter()
+        .collect();
+
+    let metrics = fleet.get_each_access_key_data_transferred();
+
+    assert!(metrics["healthy"].is_ok());
+    assert!(matches!(
+        metrics["broken"],
+        Err(APIError::CertificatePinMismatch { .. })
+    ));
+}
+
+#[test]
+fn custom_operations() {
+    let servers: Vec<MockServer> = (0..5).map(|_| MockServer::start()).collect();
+    let fleet: Fleet = servers
+        .iter()
+        .enumerate()
+        .map(|(index, server)| (format!("server-{}", index), server.client()))
+        .collect();
+
+    let names = fleet.for_each(|client| Ok(client.get_server_info()?.name));
+
+    assert_eq!(fleet.names().count(), 5);
+    assert!(names
+        .values()
+        .all(|name| name.as_deref().unwrap() == "Outline Server"));
+}
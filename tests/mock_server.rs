@@ -0,0 +1,221 @@
+#![cfg(feature = "blocking")]
+
+use outline_api::mock::MockServer;
+use outline_api::{APIError, CreateAccessKeyRequest, DataLimit, OutlineVPN, RetryPolicy};
+use std::num::NonZeroU16;
+use std::time::Duration;
+
+#[test]
+fn server_settings() {
+    let server = MockServer::builder().name("Test").start();
+    let vpn = server.client();
+
+    assert_eq!(vpn.get_server_info().unwrap().name, "Test");
+
+    vpn.rename_server("Renamed").unwrap();
+    vpn.change_hostname_for_access_keys("vpn.example.com")
+        .unwrap();
+    vpn.change_default_port_for_newly_created_access(8388u16)
+        .unwrap();
+    vpn.set_data_transfer_limit_for_all_access_keys(1000)
+        .unwrap();
+
+    let info = vpn.get_server_info().unwrap();
+    assert_eq!(info.name, "Renamed");
+    assert_eq!(info.hostname_for_access_keys, "vpn.example.com");
+    assert_eq!(info.port_for_new_access_keys, 8388);
+    assert_eq!(info.access_key_data_limit, Some(DataLimit::new(1000)));
+
+    vpn.remove_data_limit_for_all_access_keys().unwrap();
+    assert_eq!(vpn.get_server_info().unwrap().access_key_data_limit, None);
+}
+
+#[test]
+fn invalid_server_settings() {
+    let server = MockServer::start();
+    let vpn = server.client();
+
+    assert!(matches!(
+        vpn.change_hostname_for_access_keys("not a hostname"),
+        Err(APIError::InvalidHostname(_))
+    ));
+    assert!(matches!(
+        vpn.rename_server(&"x".repeat(101)),
+        Err(APIError::InvalidName(_))
+    ));
+
+    server.occupy_port(8080);
+    assert!(matches!(
+        vpn.change_default_port_for_newly_created_access(8080u16),
+        Err(APIError::PortConflict(_))
+    ));
+}
+
+#[test]
+fn access_key_lifecycle() {
+    let server = MockServer::start();
+    let vpn = server.client();
+
+    let first = vpn.create_access_key().unwrap();
+    let second = vpn.create_access_key().unwrap();
+    assert_eq!(first.id, "0");
+    assert_eq!(second.id, "1");
+    assert_eq!(first.port, 12345);
+    assert!(first.access_url.starts_with("ss://"));
+
+    vpn.change_name_for_access_key(&first.id, "Alice").unwrap();
+    vpn.set_data_transfer_limit_by_id(&first.id, 5000).unwrap();
+    let key = vpn.get_access_key_by_id(&first.id).unwrap();
+    assert_eq!(key.name, "Alice");
+    assert_eq!(key.data_limit, Some(DataLimit::new(5000)));
+
+    vpn.del_data_transfer_limit_by_id(&first.id).unwrap();
+    assert_eq!(
+        vpn.get_access_key_by_id(&first.id).unwrap().data_limit,
+        None
+    );
+
+    vpn.delete_access_key_by_id(&second.id).unwrap();
+    let keys = vpn.list_access_keys().unwrap().access_keys;
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].id, first.id);
+}
+
+#[test]
+fn missing_access_keys() {
+    let server = MockServer::start();
+    let vpn = server.client();
+
+    assert!(matches!(
+        vpn.get_access_key_by_id("42"),
+        Err(APIError::AccessKeyInexistent(_))
+    ));
+    assert!(matches!(
+        vpn.delete_access_key_by_id("42"),
+        Err(APIError::AccessKeyInexistent(_))
+    ));
+    assert!(matches!(
+        vpn.change_name_for_access_key("42", "name"),
+        Err(APIError::AccessKeyInexistent(_))
+    ));
+    assert!(matches!(
+        vpn.set_data_transfer_limit_by_id("42", 1),
+        Err(APIError::AccessKeyInexistent(_))
+    ));
+    assert!(matches!(
+        vpn.del_data_transfer_limit_by_id("42"),
+        Err(APIError::AccessKeyInexistent(_))
+    ));
+}
+
+#[test]
+fn create_access_key_with_options_and_id() {
+    let server = MockServer::start();
+    let vpn = server.client();
+    let request = CreateAccessKeyRequest {
+        name: Some("Bob".to_string()),
+        method: Some("aes-192-gcm".to_string()),
+        port: NonZeroU16::new(9000),
+        password: Some("secret".to_string()),
+        limit: Some(DataLimit::new(10)),
+    };
+
+    let key = vpn
+        .create_access_key_with_id("customer/42", &request)
+        .unwrap();
+    assert_eq!(key.id, "customer/42");
+    assert_eq!(key.name, "Bob");
+    assert_eq!(key.method, "aes-192-gcm");
+    assert_eq!(key.port, 9000);
+    assert_eq!(key.password, "secret");
+    assert_eq!(key.data_limit, Some(DataLimit::new(10)));
+    assert_eq!(vpn.get_access_key_by_id("customer/42").unwrap(), key);
+
+    assert!(matches!(
+        vpn.create_access_key_with_id("customer/42", &request),
+        Err(APIError::AccessKeyAlreadyExists(_))
+    ));
+
+    let unsupported = CreateAccessKeyRequest {
+        method: Some("rc4".to_string()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        vpn.create_access_key_with_options(&unsupported),
+        Err(APIError::InvalidRequest(_))
+    ));
+}
+
+#[test]
+fn legacy_server_falls_back_to_list() {
+    let server = MockServer::builder().legacy(true).start();
+    let vpn = server.client();
+    let key = vpn.create_access_key().unwrap();
+
+    assert_eq!(vpn.get_access_key_by_id(&key.id).unwrap(), key);
+    assert!(matches!(
+        vpn.get_access_key_by_id("42"),
+        Err(APIError::AccessKeyInexistent(_))
+    ));
+    assert!(vpn
+        .create_access_key_with_id("custom", &CreateAccessKeyRequest::default())
+        .is_err());
+}
+
+#[test]
+fn metrics() {
+    let server = MockServer::start();
+    let vpn = server.client();
+    let key = vpn.create_access_key().unwrap();
+    server.set_bytes_transferred(&key.id, 1024);
+
+    let metrics = vpn.get_each_access_key_data_transferred().unwrap();
+    assert_eq!(metrics.bytes_transferred_by_user_id[&key.id], 1024);
+
+    assert!(
+        !vpn.get_whether_metrics_is_being_shared()
+            .unwrap()
+            .metrics_enabled
+    );
+    vpn.enable_or_disable_sharing_metrics(true).unwrap();
+    assert!(
+        vpn.get_whether_metrics_is_being_shared()
+            .unwrap()
+            .metrics_enabled
+    );
+}
+
+#[test]
+fn wrong_secret_is_not_found() {
+    let server = MockServer::start();
+    let api_url = server.api_url().rsplit_once('/').unwrap().0.to_string() + "/wrong";
+    let vpn = OutlineVPN::builder()
+        .api_url(api_url)
+        .cert_sha256(server.cert_sha256())
+        .build()
+        .unwrap();
+
+    assert!(vpn.get_server_info().is_err());
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn records_requests_and_injects_failures() {
+    let server = MockServer::start();
+    let vpn = OutlineVPN::builder()
+        .access_config(&server.access_config())
+        .unwrap()
+        .retry_policy(RetryPolicy::new(3).initial_backoff(Duration::from_millis(1)))
+        .build()
+        .unwrap();
+    server.fail_next_requests(2, 503);
+
+    vpn.rename_server("After retries").unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(requests
+        .iter()
+        .all(|request| request.method == "PUT" && request.path == "/name"));
+    assert_eq!(server.server().name, "After retries");
+}
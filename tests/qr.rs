@@ -0,0 +1,68 @@
+#![cfg(feature = "qr")]
+
+use outline_api::QrCode;
+
+const ACCESS_URL: &str =
+    "ss://Y2hhY2hhMjAtaWV0Zi1wb2x5MTMwNTpYeFh4WHg=@203.0.113.7:18162/?outline=1";
+
+#[test]
+fn png_is_a_grayscale_image_with_quiet_zone() {
+    let code = QrCode::new(ACCESS_URL).unwrap();
+    let png = code.to_png(4).unwrap();
+
+    let decoder = png::Decoder::new(png.as_slice());
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut pixels).unwrap();
+
+    let side = (code.width() as u32 + 8) * 4;
+    assert_eq!((frame.width, frame.height), (side, side));
+    assert_eq!(frame.color_type, png::ColorType::Grayscale);
+    // The quiet zone is white and the top-left finder pattern starts right after it
+    assert!(pixels[..(side * 16) as usize]
+        .iter()
+        .all(|pixel| *pixel == 0xff));
+    assert_eq!(pixels[(16 * side + 16) as usize], 0);
+}
+
+#[test]
+fn svg_and_unicode_render_every_module() {
+    let code = QrCode::new(ACCESS_URL).unwrap();
+
+    let svg = code.to_svg(8).unwrap();
+    assert!(svg.contains("<svg"));
+    assert!(svg.ends_with("</svg>"));
+
+    // Two module rows per line, plus the quiet zone above and below
+    let unicode = code.to_unicode();
+    assert_eq!(unicode.lines().count(), (code.width() + 8 + 1) / 2);
+}
+
+#[test]
+fn oversized_images_are_rejected() {
+    let code = QrCode::new(ACCESS_URL).unwrap();
+
+    assert!(code.to_png(2000).is_err());
+    assert!(code.to_png(u32::MAX).is_err());
+    assert!(code.to_svg(2000).is_err());
+    assert!(code.to_svg(u32::MAX).is_err());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn access_key_qr_code() {
+    let server = outline_api::mock::MockServer::start();
+    let key = server.client().create_access_key().unwrap();
+
+    let code = key.qr_code().unwrap();
+
+    assert_eq!(
+        code.to_png(2).unwrap(),
+        QrCode::new(&key.access_url).unwrap().to_png(2).unwrap()
+    );
+}
+
+#[test]
+fn oversized_data_is_rejected() {
+    assert!(QrCode::new("x".repeat(5000)).is_err());
+}
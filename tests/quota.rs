@@ -0,0 +1,191 @@
+mod common;
+
+use common::{access_key, snapshot};
+use outline_api::{AccessKeyId, DataLimit, Operation, Quota, QuotaEngine, QuotaError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY: u64 = 24 * 60 * 60;
+// 2024-01-01T00:00:00Z
+const JAN_1_2024: u64 = 1_704_067_200;
+
+/// Midnight UTC of a day of 2024, counted from January 1st as day 0.
+fn day_of_2024(day: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(JAN_1_2024 + day * DAY)
+}
+
+fn set_limit(id: &str, from: Option<u64>, to: u64) -> Operation {
+    Operation::SetLimit {
+        id: AccessKeyId::from(id),
+        from: from.map(DataLimit::new),
+        to: DataLimit::new(to),
+    }
+}
+
+#[test]
+fn computes_periods() {
+    let quota = Quota::monthly(0).with_reset_day(15).unwrap();
+    // 2024-02-20 is in the period starting 2024-02-15
+    assert_eq!(quota.period_start(day_of_2024(50)), day_of_2024(45));
+    assert_eq!(quota.next_reset(day_of_2024(50)), day_of_2024(74));
+    // 2024-01-10 is in the period starting 2023-12-15
+    assert_eq!(
+        quota.period_start(day_of_2024(9)),
+        day_of_2024(0) - Duration::from_secs(17 * DAY)
+    );
+    // The reset day itself starts a new period
+    assert_eq!(
+        quota.period_start(day_of_2024(45) + Duration::from_secs(1)),
+        day_of_2024(45)
+    );
+
+    // The 31st resets on 2024-02-29, a leap day, then on 2024-03-31
+    let quota = Quota::monthly(0).with_reset_day(31).unwrap();
+    assert_eq!(quota.period_start(day_of_2024(59)), day_of_2024(59));
+    assert_eq!(quota.period_start(day_of_2024(58)), day_of_2024(30));
+    assert_eq!(quota.next_reset(day_of_2024(59)), day_of_2024(90));
+    // Across the end of the year
+    assert_eq!(
+        Quota::monthly(0).next_reset(day_of_2024(365)),
+        UNIX_EPOCH + Duration::from_secs(1_735_689_600)
+    );
+}
+
+#[test]
+fn limits_keys_to_count_at_period_start_plus_allowance() {
+    let engine = QuotaEngine::new()
+        .default_quota(Quota::monthly(1000))
+        .with_quota("2", Quota::monthly(50).with_reset_day(10).unwrap());
+    let snapshots = [
+        snapshot(day_of_2024(0), &[("0", 300), ("1", 0), ("2", 100)]),
+        snapshot(day_of_2024(9), &[("0", 500), ("1", 2000), ("2", 120)]),
+        snapshot(day_of_2024(20), &[("0", 700), ("1", 2000), ("2", 160)]),
+    ];
+    let keys = [
+        access_key("0", "", None),
+        access_key("1", "", Some(1000)),
+        access_key("2", "", None),
+    ];
+
+    let plan = engine.plan(&snapshots, &keys, day_of_2024(20));
+
+    assert_eq!(
+        plan.operations,
+        [
+            set_limit("0", None, 1300),
+            // Already over the allowance, so limited to what it has now
+            set_limit("1", Some(1000), 2000),
+            // Its period started on January 10th, at a count of 120
+            set_limit("2", None, 170),
+        ]
+    );
+}
+
+#[test]
+fn raises_limits_when_period_rolls_over() {
+    let engine = QuotaEngine::new().default_quota(Quota::monthly(1000));
+    let snapshots = [
+        snapshot(day_of_2024(0), &[("0", 0)]),
+        snapshot(day_of_2024(30), &[("0", 1000)]),
+        snapshot(day_of_2024(31), &[("0", 1000)]),
+    ];
+
+    // February 1st: January's usage no longer counts
+    let plan = engine.plan(
+        &snapshots,
+        &[access_key("0", "", Some(1000))],
+        day_of_2024(31),
+    );
+    assert_eq!(plan.operations, [set_limit("0", Some(1000), 2000)]);
+
+    // Nothing changes once the limit is set
+    let plan = engine.plan(
+        &snapshots,
+        &[access_key("0", "", Some(2000))],
+        day_of_2024(31),
+    );
+    assert!(plan.is_empty());
+}
+
+#[test]
+fn follows_rolling_window_and_skips_unknown_keys() {
+    let engine = QuotaEngine::new().with_quota("0", Quota::monthly(1000));
+    let snapshots = [
+        snapshot(day_of_2024(31), &[("0", 5000), ("1", 10)]),
+        // Old traffic left the window while the key used 200 bytes
+        snapshot(day_of_2024(40), &[("0", 3000), ("1", 10)]),
+    ];
+    let keys = [
+        access_key("0", "", None),
+        access_key("1", "", None),
+        access_key("2", "", None),
+    ];
+
+    let plan = engine.plan(&snapshots, &keys, day_of_2024(40));
+
+    // Keys without a quota, or missing from the last snapshot, are left alone
+    assert_eq!(plan.operations, [set_limit("0", None, 4000)]);
+}
+
+#[test]
+fn lifts_limits_of_keys_without_a_quota_on_request() {
+    let snapshots = [snapshot(day_of_2024(40), &[("0", 3000), ("1", 10)])];
+    // Key 0 was limited by an earlier run, then its quota was removed from the config
+    let keys = [
+        access_key("0", "", Some(1500)),
+        access_key("1", "", Some(500)),
+    ];
+    let engine = QuotaEngine::new().with_quota("1", Quota::monthly(490));
+
+    // Limits of keys without a quota are left alone by default
+    assert!(engine.plan(&snapshots, &keys, day_of_2024(40)).is_empty());
+    assert_eq!(
+        engine
+            .remove_unlisted_limits(true)
+            .plan(&snapshots, &keys, day_of_2024(40))
+            .operations,
+        [Operation::RemoveLimit {
+            id: AccessKeyId::from("0"),
+            from: DataLimit::new(1500),
+        }]
+    );
+}
+
+#[test]
+fn rejects_reset_days_outside_the_month() {
+    for day in [0, 32] {
+        assert!(matches!(
+            Quota::monthly(0).with_reset_day(day),
+            Err(QuotaError::InvalidResetDay(invalid)) if invalid == day
+        ));
+        let json = format!(r#"{{"allowance":100,"resetDay":{}}}"#, day);
+        assert!(serde_json::from_str::<Quota>(&json).is_err());
+    }
+    let quota: Quota = serde_json::from_str(r#"{"allowance":100}"#).unwrap();
+    assert_eq!(quota.reset_day(), 1);
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn plans_for_a_server() {
+    let server = outline_api::mock::MockServer::start();
+    let vpn = server.client();
+    vpn.create_access_key().unwrap();
+    server.set_bytes_transferred("0", 250);
+    let path = std::env::temp_dir().join(format!("outline-api-quota-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let recorder = outline_api::Recorder::new(&path);
+
+    let engine = QuotaEngine::new().default_quota(Quota::monthly(1000));
+    let plan = engine.plan_for(&vpn, &recorder).unwrap();
+    assert!(plan
+        .apply(&vpn)
+        .iter()
+        .all(|outcome| outcome.result.is_ok()));
+
+    assert_eq!(
+        vpn.get_access_key_by_id("0").unwrap().data_limit,
+        Some(DataLimit::new(1250))
+    );
+    assert_eq!(recorder.snapshots().unwrap().len(), 1);
+    std::fs::remove_file(&path).unwrap();
+}
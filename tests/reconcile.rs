@@ -0,0 +1,255 @@
+mod common;
+
+use common::access_key;
+use outline_api::{
+    AccessKeyId, DataLimit, DesiredKey, Operation, Plan, ReconcileError, Reconciler,
+};
+
+#[test]
+fn no_changes_when_matching() {
+    let existing = [
+        access_key("0", "Alice", Some(10)),
+        access_key("1", "Bob", None),
+    ];
+    let desired = [
+        DesiredKey::named("Alice").limit(DataLimit::new(10)),
+        DesiredKey::with_id("1", "Bob"),
+    ];
+
+    let plan = Reconciler::new(desired).unwrap().plan(&existing);
+
+    assert!(plan.is_empty());
+    assert_eq!(plan.to_string(), "No changes");
+}
+
+#[test]
+fn plans_every_kind_of_operation() {
+    let existing = [
+        access_key("0", "Alice", None),
+        access_key("1", "Robert", Some(5)),
+        access_key("2", "Carol", Some(5)),
+        access_key("3", "Mallory", None),
+    ];
+    let desired = [
+        DesiredKey::named("Alice").limit(DataLimit::new(10)),
+        DesiredKey::with_id("1", "Bob").limit(DataLimit::new(7)),
+        DesiredKey::named("Carol"),
+        DesiredKey::named("Dave"),
+        DesiredKey::with_id("eve", "Eve"),
+    ];
+
+    let plan = Reconciler::new(desired).unwrap().plan(&existing);
+
+    assert_eq!(
+        plan.operations,
+        [
+            Operation::SetLimit {
+                id: "0".into(),
+                from: None,
+                to: DataLimit::new(10),
+            },
+            Operation::Rename {
+                id: "1".into(),
+                from: "Robert".to_string(),
+                to: "Bob".to_string(),
+            },
+            Operation::SetLimit {
+                id: "1".into(),
+                from: Some(DataLimit::new(5)),
+                to: DataLimit::new(7),
+            },
+            Operation::RemoveLimit {
+                id: "2".into(),
+                from: DataLimit::new(5),
+            },
+            Operation::Create {
+                id: None,
+                name: "Dave".to_string(),
+                limit: None,
+            },
+            Operation::Create {
+                id: Some("eve".into()),
+                name: "Eve".to_string(),
+                limit: None,
+            },
+            Operation::Delete {
+                id: "3".into(),
+                name: "Mallory".to_string(),
+            },
+        ]
+    );
+    assert_eq!(
+        plan.to_string(),
+        [
+            r#"~ set limit of key "0" to 10 B"#,
+            r#"~ rename key "1" from "Robert" to "Bob""#,
+            r#"~ change limit of key "1" from 5 B to 7 B"#,
+            r#"~ remove limit of 5 B from key "2""#,
+            r#"+ create key "Dave""#,
+            r#"+ create key "Eve" with ID "eve""#,
+            r#"- delete key "3" ("Mallory")"#,
+        ]
+        .join("\n")
+    );
+}
+
+#[test]
+fn duplicate_names_match_one_key_each() {
+    let existing = [
+        access_key("0", "Guest", None),
+        access_key("1", "Guest", None),
+    ];
+    let desired = [
+        DesiredKey::named("Guest"),
+        DesiredKey::named("Guest"),
+        DesiredKey::named("Guest"),
+    ];
+
+    let plan = Reconciler::new(desired).unwrap().plan(&existing);
+
+    assert_eq!(
+        plan.operations,
+        [Operation::Create {
+            id: None,
+            name: "Guest".to_string(),
+            limit: None,
+        }]
+    );
+}
+
+#[test]
+fn keeps_unlisted_keys_when_asked() {
+    let existing = [access_key("0", "Alice", None)];
+
+    let plan = Reconciler::new([])
+        .unwrap()
+        .delete_unlisted(false)
+        .plan(&existing);
+
+    assert_eq!(plan, Plan::default());
+}
+
+#[test]
+fn rejects_duplicate_ids() {
+    let desired = [
+        DesiredKey::with_id("7", "Bob"),
+        DesiredKey::named("Bob"),
+        DesiredKey::with_id("7", "Robert"),
+    ];
+
+    assert_eq!(
+        Reconciler::new(desired).unwrap_err(),
+        ReconcileError::DuplicateId(AccessKeyId::from("7"))
+    );
+    assert!(Reconciler::new([DesiredKey::named("Bob"), DesiredKey::named("Bob")]).is_ok());
+}
+
+#[test]
+fn desired_keys_deserialize_from_config() {
+    let desired: Vec<DesiredKey> = serde_json::from_str(
+        r#"[{"name": "Alice", "limit": {"bytes": 100}}, {"id": "7", "name": "Bob"}]"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        desired,
+        [
+            DesiredKey::named("Alice").limit(DataLimit::new(100)),
+            DesiredKey::with_id("7", "Bob"),
+        ]
+    );
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn applies_plan_against_server() {
+    let server = outline_api::mock::MockServer::start();
+    let vpn = server.client();
+    vpn.create_access_key().unwrap();
+    let stale = vpn.create_access_key().unwrap();
+    vpn.change_name_for_access_key(&stale.id, "Stale").unwrap();
+    let desired = [
+        DesiredKey::named("Alice").limit(DataLimit::new(1000)),
+        DesiredKey::with_id("bob", "Bob"),
+        DesiredKey::with_id("0", "Renamed"),
+    ];
+
+    let outcomes = Reconciler::new(desired.clone())
+        .unwrap()
+        .plan_for(&vpn)
+        .unwrap()
+        .apply(&vpn);
+
+    assert!(outcomes.iter().all(|outcome| outcome.result.is_ok()));
+    let keys = server.access_keys();
+    let summary: Vec<(&str, Option<DataLimit>)> = keys
+        .iter()
+        .map(|key| (key.name.as_str(), key.data_limit))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("Renamed", None),
+            ("Alice", Some(DataLimit::new(1000))),
+            ("Bob", None),
+        ]
+    );
+    assert_eq!(keys[0].id, "0");
+    assert_eq!(keys[2].id, "bob");
+    assert!(Reconciler::new(desired)
+        .unwrap()
+        .plan_for(&vpn)
+        .unwrap()
+        .is_empty());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn reports_failed_steps_and_continues() {
+    let server = outline_api::mock::MockServer::builder()
+        .legacy(true)
+        .start();
+    let vpn = server.client();
+    let desired = [
+        DesiredKey::with_id("bob", "Bob"),
+        DesiredKey::named("Alice"),
+    ];
+
+    let outcomes = Reconciler::new(desired)
+        .unwrap()
+        .plan_for(&vpn)
+        .unwrap()
+        .apply(&vpn);
+
+    assert_eq!(outcomes.len(), 2);
+    assert!(outcomes[0].result.is_err());
+    assert!(outcomes[1].result.is_ok());
+    assert_eq!(server.access_keys()[0].name, "Alice");
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn names_and_limits_keys_on_servers_ignoring_create_fields() {
+    let server = outline_api::mock::MockServer::builder()
+        .legacy(true)
+        .start();
+    let vpn = server.client();
+    let desired = [DesiredKey::named("Alice").limit(DataLimit::new(1000))];
+
+    let outcomes = Reconciler::new(desired.clone())
+        .unwrap()
+        .plan_for(&vpn)
+        .unwrap()
+        .apply(&vpn);
+
+    assert!(outcomes.iter().all(|outcome| outcome.result.is_ok()));
+    let keys = server.access_keys();
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].name, "Alice");
+    assert_eq!(keys[0].data_limit, Some(DataLimit::new(1000)));
+    assert!(Reconciler::new(desired)
+        .unwrap()
+        .plan_for(&vpn)
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,215 @@
+mod common;
+
+use common::snapshot;
+use outline_api::{AccessKeyId, Recorder, Snapshot};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn at(seconds: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+fn with_fingerprints(mut snapshot: Snapshot, fingerprints: &[(&str, &str)]) -> Snapshot {
+    snapshot.key_fingerprints = fingerprints
+        .iter()
+        .map(|(id, fingerprint)| (AccessKeyId::from(*id), fingerprint.to_string()))
+        .collect();
+    snapshot
+}
+
+fn usage(entries: &[(&str, u64)]) -> BTreeMap<AccessKeyId, u64> {
+    entries
+        .iter()
+        .map(|(id, bytes)| (AccessKeyId::from(*id), *bytes))
+        .collect()
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("outline-api-{}-{}.jsonl", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn sums_increases_between_snapshots() {
+    let snapshots = [
+        snapshot(at(0), &[("0", 100)]),
+        snapshot(at(10), &[("0", 150), ("1", 0)]),
+        snapshot(at(20), &[("0", 400), ("1", 30)]),
+        snapshot(at(30), &[("0", 1000), ("1", 30)]),
+    ];
+
+    // Starts from the last snapshot at or before `from`
+    assert_eq!(
+        Snapshot::usage(&snapshots, at(15), at(25)),
+        usage(&[("0", 250), ("1", 30)])
+    );
+    assert_eq!(
+        Snapshot::usage(&snapshots, at(0), at(30)),
+        usage(&[("0", 900), ("1", 30)])
+    );
+    // Without an earlier snapshot, the first one of the period is the baseline
+    assert_eq!(
+        Snapshot::usage(&snapshots[1..], at(0), at(20)),
+        usage(&[("0", 250), ("1", 30)])
+    );
+    assert!(Snapshot::usage(&snapshots, at(40), at(50)).is_empty());
+}
+
+#[test]
+fn handles_recreated_keys_and_rolling_window() {
+    let snapshots = [
+        snapshot(at(0), &[("0", 500), ("1", 800)]),
+        // Key 0 was deleted, and old traffic of key 1 left the window
+        snapshot(at(10), &[("1", 200)]),
+        // Key 0 was recreated with the same ID
+        snapshot(at(20), &[("0", 70), ("1", 260)]),
+    ];
+
+    assert_eq!(
+        Snapshot::usage(&snapshots, at(0), at(20)),
+        usage(&[("0", 70), ("1", 60)])
+    );
+}
+
+#[test]
+fn counts_keys_recreated_between_snapshots_in_full() {
+    let snapshots = [
+        with_fingerprints(
+            snapshot(at(0), &[("0", 500), ("1", 800)]),
+            &[("0", "aaaa"), ("1", "bbbb")],
+        ),
+        // Key 0 was deleted and recreated with the same ID, with no snapshot in between, while
+        // old traffic of key 1 left the window
+        with_fingerprints(
+            snapshot(at(10), &[("0", 70), ("1", 200)]),
+            &[("0", "cccc"), ("1", "bbbb")],
+        ),
+    ];
+
+    assert_eq!(
+        Snapshot::usage(&snapshots, at(0), at(10)),
+        usage(&[("0", 70), ("1", 0)])
+    );
+}
+
+#[test]
+fn only_compares_fingerprints_with_the_same_salt() {
+    let mut salted = with_fingerprints(snapshot(at(10), &[("0", 70)]), &[("0", "cccc")]);
+    salted.fingerprint_salt = "salt".to_string();
+    let snapshots = [
+        with_fingerprints(snapshot(at(0), &[("0", 50)]), &[("0", "aaaa")]),
+        salted,
+    ];
+
+    assert_eq!(
+        Snapshot::usage(&snapshots, at(0), at(10)),
+        usage(&[("0", 20)])
+    );
+}
+
+#[test]
+fn keeps_the_salt_of_the_file() {
+    let path = temp_file("salt");
+    let recorder = Recorder::new(&path);
+    let salt = recorder.salt().unwrap();
+    assert_eq!(salt.len(), 32);
+    assert_ne!(recorder.salt().unwrap(), salt);
+
+    let mut recorded = snapshot(at(10), &[("0", 100)]);
+    recorded.fingerprint_salt = salt.clone();
+    recorder.append(&recorded).unwrap();
+    recorder.append(&snapshot(at(20), &[("0", 300)])).unwrap();
+
+    assert_eq!(recorder.salt().unwrap(), salt);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn appends_and_reads_snapshots() {
+    let path = temp_file("recorder");
+    let recorder = Recorder::new(&path);
+    assert!(recorder.snapshots().unwrap().is_empty());
+
+    recorder.append(&snapshot(at(20), &[("0", 300)])).unwrap();
+    recorder.append(&snapshot(at(10), &[("0", 100)])).unwrap();
+    // A line cut short by a crash is skipped
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| std::io::Write::write_all(&mut file, b"{\"timestampMs\":3"))
+        .unwrap();
+
+    let snapshots = recorder.snapshots().unwrap();
+    assert_eq!(
+        snapshots,
+        [
+            snapshot(at(10), &[("0", 100)]),
+            snapshot(at(20), &[("0", 300)])
+        ]
+    );
+    assert_eq!(recorder.usage(at(0), at(60)).unwrap(), usage(&[("0", 200)]));
+    assert!(std::fs::read_to_string(&path)
+        .unwrap()
+        .starts_with("{\"timestampMs\":20000,\"bytesTransferred\":{\"0\":300}}\n"));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn takes_snapshots_of_listed_keys() {
+    let server = outline_api::mock::MockServer::start();
+    let vpn = server.client();
+    vpn.create_access_key().unwrap();
+    vpn.create_access_key().unwrap();
+    server.set_bytes_transferred("1", 4096);
+    // Traffic of a deleted key is still reported by the server
+    server.set_bytes_transferred("7", 1);
+
+    let snapshot = Snapshot::take(&vpn, "salt").unwrap();
+
+    assert_eq!(snapshot.bytes_transferred, usage(&[("0", 0), ("1", 4096)]));
+    assert!(snapshot.timestamp() <= SystemTime::now());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn tells_recreated_keys_apart_on_a_server() {
+    let server = outline_api::mock::MockServer::start();
+    let vpn = server.client();
+    let request = outline_api::CreateAccessKeyRequest::default();
+    let key = vpn.create_access_key_with_id("alice", &request).unwrap();
+    server.set_bytes_transferred("alice", 500);
+    let before = Snapshot::take(&vpn, "salt").unwrap();
+    vpn.delete_access_key_by_id("alice").unwrap();
+    vpn.create_access_key_with_id("alice", &request).unwrap();
+    server.set_bytes_transferred("alice", 70);
+    let after = Snapshot::take(&vpn, "salt").unwrap();
+
+    let fingerprint = &before.key_fingerprints[&key.id];
+    assert!(!fingerprint.contains(&key.password));
+    assert_ne!(fingerprint, &after.key_fingerprints[&key.id]);
+    // The same password hashes differently with another salt
+    let resalted = Snapshot::new(
+        before.timestamp(),
+        &vpn.list_access_keys().unwrap().access_keys,
+        &vpn.get_each_access_key_data_transferred().unwrap(),
+        "pepper",
+    );
+    assert_ne!(
+        after.key_fingerprints[&key.id],
+        resalted.key_fingerprints[&key.id]
+    );
+    assert_eq!(
+        Snapshot::usage(
+            &[before.clone(), after.clone()],
+            before.timestamp(),
+            after.timestamp()
+        ),
+        usage(&[("alice", 70)])
+    );
+}
@@ -0,0 +1,156 @@
+#![cfg(feature = "blocking")]
+
+mod common;
+
+use common::{spawn_api_capture, CapturedRequest};
+use outline_api::{APIError, CreateAccessKeyRequest, OutlineVPN};
+use serde_json::{json, Value};
+use std::num::NonZeroU16;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+const HOSTILE_NAMES: &[&str] = &[
+    "plain",
+    "quote \" inside",
+    "trailing backslash \\",
+    r#"", "injected": true, "name": ""#,
+    "new\nline\ttab\r",
+    "nul \u{0} and bell \u{7}",
+    "emoji 🔐🇺🇦👩‍💻",
+    "right-to-left \u{202e}txet",
+    "{{ \"name\": \"{}\" }}",
+    "",
+];
+
+fn client() -> (OutlineVPN, Receiver<CapturedRequest>) {
+    let stub = spawn_api_capture();
+    let vpn = OutlineVPN::builder()
+        .api_url(stub.api_url)
+        .cert_sha256(stub.cert_sha256)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+    (vpn, stub.requests)
+}
+
+fn body_of(requests: &Receiver<CapturedRequest>) -> Value {
+    let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+    serde_json::from_str(&request.body).unwrap()
+}
+
+#[test]
+fn rename_server_round_trips_hostile_names() {
+    let (vpn, requests) = client();
+
+    for name in HOSTILE_NAMES {
+        vpn.rename_server(name).unwrap();
+
+        assert_eq!(body_of(&requests), json!({ "name": name }));
+    }
+}
+
+#[test]
+fn change_name_for_access_key_round_trips_hostile_names() {
+    let (vpn, requests) = client();
+
+    for name in HOSTILE_NAMES {
+        vpn.change_name_for_access_key("1", name).unwrap();
+
+        assert_eq!(body_of(&requests), json!({ "name": name }));
+    }
+}
+
+#[test]
+fn change_hostname_round_trips_hostile_input() {
+    let (vpn, requests) = client();
+
+    for hostname in HOSTILE_NAMES {
+        vpn.change_hostname_for_access_keys(hostname).unwrap();
+
+        assert_eq!(body_of(&requests), json!({ "hostname": hostname }));
+    }
+}
+
+#[test]
+fn create_access_key_round_trips_hostile_options() {
+    let (vpn, requests) = client();
+
+    for name in HOSTILE_NAMES {
+        let request = CreateAccessKeyRequest {
+            name: Some(name.to_string()),
+            password: Some(name.to_string()),
+            ..Default::default()
+        };
+        // The capture stub doesn't return a key, only the request matters here
+        let _ = vpn.create_access_key_with_options(&request);
+
+        assert_eq!(
+            body_of(&requests),
+            json!({ "name": name, "password": name })
+        );
+    }
+}
+
+#[test]
+fn access_key_ids_are_percent_encoded() {
+    let (vpn, requests) = client();
+
+    vpn.change_name_for_access_key("customer/42?x=#1 é", "name")
+        .unwrap();
+
+    let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(request.method, "PUT");
+    assert_eq!(
+        request.path,
+        "/secret/access-keys/customer%2F42%3Fx%3D%231%20%C3%A9/name"
+    );
+}
+
+#[test]
+fn data_limit_and_metrics_bodies() {
+    let (vpn, requests) = client();
+
+    vpn.set_data_transfer_limit_for_all_access_keys(u64::MAX)
+        .unwrap();
+    assert_eq!(
+        body_of(&requests),
+        json!({ "limit": { "bytes": u64::MAX } })
+    );
+
+    vpn.set_data_transfer_limit_by_id(7u16, 10_000).unwrap();
+    assert_eq!(body_of(&requests), json!({ "limit": { "bytes": 10_000 } }));
+
+    vpn.enable_or_disable_sharing_metrics(false).unwrap();
+    assert_eq!(body_of(&requests), json!({ "metricsEnabled": false }));
+}
+
+#[test]
+fn port_bodies() {
+    let (vpn, requests) = client();
+
+    vpn.change_default_port_for_newly_created_access(12345u16)
+        .unwrap();
+    assert_eq!(body_of(&requests), json!({ "port": 12345 }));
+
+    vpn.change_default_port_for_newly_created_access(NonZeroU16::MAX)
+        .unwrap();
+    assert_eq!(body_of(&requests), json!({ "port": 65535 }));
+
+    let request = CreateAccessKeyRequest {
+        port: NonZeroU16::new(8388),
+        ..Default::default()
+    };
+    let _ = vpn.create_access_key_with_options(&request);
+    assert_eq!(body_of(&requests), json!({ "port": 8388 }));
+}
+
+#[test]
+fn port_zero_is_rejected_locally() {
+    let (vpn, requests) = client();
+
+    match vpn.change_default_port_for_newly_created_access(0u16) {
+        Err(APIError::InvalidArgument { name, .. }) => assert_eq!(name, "port"),
+        other => panic!("expected an invalid argument, got {:?}", other),
+    }
+    assert!(requests.recv_timeout(Duration::from_millis(200)).is_err());
+}
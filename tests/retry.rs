@@ -0,0 +1,147 @@
+#![cfg(feature = "blocking")]
+
+mod common;
+
+use common::{spawn_api_stub, CapturedRequest, SERVER_INFO};
+use outline_api::{APIError, CreateAccessKeyRequest, OutlineVPN, RetryPolicy};
+use reqwest::StatusCode;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+const CERT_SHA256: &str = "E2DE8E9A0A5BD9E8B3A8A5A9D5C8F8E2DE8E9A0A5BD9E8B3A8A5A9D5C8F82A75";
+
+fn fast_policy(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy::new(max_attempts)
+        .initial_backoff(Duration::from_millis(1))
+        .max_backoff(Duration::from_millis(5))
+}
+
+/// Serves the given statuses in order, then `200` with the server information.
+fn client(
+    statuses: Vec<u16>,
+    policy: Option<RetryPolicy>,
+) -> (OutlineVPN, Receiver<CapturedRequest>) {
+    let mut statuses = statuses.into_iter();
+    let stub = spawn_api_stub(move |_| match statuses.next() {
+        Some(status) => (status, r#"{"code":"Stub","message":"failure"}"#.to_string()),
+        None => (200, SERVER_INFO.to_string()),
+    });
+    let mut builder = OutlineVPN::builder()
+        .api_url(stub.api_url)
+        .cert_sha256(stub.cert_sha256)
+        .timeout(Duration::from_secs(5));
+    if let Some(policy) = policy {
+        builder = builder.retry_policy(policy);
+    }
+    (builder.build().unwrap(), stub.requests)
+}
+
+#[test]
+fn retries_get_until_success() {
+    let (vpn, requests) = client(vec![500, 503], Some(fast_policy(3)));
+
+    let server = vpn.get_server_info().unwrap();
+
+    assert_eq!(server.name, "My Server");
+    assert_eq!(requests.try_iter().count(), 3);
+}
+
+#[test]
+fn gives_up_after_max_attempts() {
+    let (vpn, requests) = client(vec![500, 500, 500], Some(fast_policy(2)));
+
+    assert!(matches!(
+        vpn.get_server_info(),
+        Err(APIError::InternalError(_))
+    ));
+    assert_eq!(requests.try_iter().count(), 2);
+}
+
+#[test]
+fn retries_put_and_delete() {
+    let (vpn, requests) = client(vec![502, 204, 504], Some(fast_policy(3)));
+
+    vpn.rename_server("name").unwrap();
+    vpn.del_data_transfer_limit_by_id("1").unwrap();
+
+    let methods: Vec<String> = requests.try_iter().map(|request| request.method).collect();
+    assert_eq!(methods, ["PUT", "PUT", "DELETE", "DELETE"]);
+}
+
+#[test]
+fn never_retries_post() {
+    let (vpn, requests) = client(vec![500], Some(fast_policy(3)));
+
+    assert!(matches!(
+        vpn.create_access_key(),
+        Err(APIError::InternalError(_))
+    ));
+    assert_eq!(requests.try_iter().count(), 1);
+}
+
+#[test]
+fn never_retries_create_access_key_with_id() {
+    let (vpn, requests) = client(vec![503], Some(fast_policy(3)));
+
+    assert!(matches!(
+        vpn.create_access_key_with_id("custom", &CreateAccessKeyRequest::default()),
+        Err(APIError::UnknownError(_))
+    ));
+    assert_eq!(requests.try_iter().count(), 1);
+}
+
+#[test]
+fn never_retries_delete_access_key_by_id() {
+    let (vpn, requests) = client(vec![503], Some(fast_policy(3)));
+
+    assert!(matches!(
+        vpn.delete_access_key_by_id("1"),
+        Err(APIError::UnknownError(_))
+    ));
+    assert_eq!(requests.try_iter().count(), 1);
+}
+
+#[test]
+fn does_not_retry_other_statuses() {
+    let (vpn, requests) = client(vec![404], Some(fast_policy(3)));
+
+    assert!(vpn.get_server_info().is_err());
+    assert_eq!(requests.try_iter().count(), 1);
+}
+
+#[test]
+fn retryable_statuses_are_configurable() {
+    let policy = fast_policy(3).retryable_statuses([StatusCode::CONFLICT]);
+    let (vpn, requests) = client(vec![409, 500], Some(policy));
+
+    assert!(matches!(
+        vpn.get_server_info(),
+        Err(APIError::InternalError(_))
+    ));
+    assert_eq!(requests.try_iter().count(), 2);
+}
+
+#[test]
+fn sends_once_without_policy() {
+    let (vpn, requests) = client(vec![500], None);
+
+    assert!(vpn.get_server_info().is_err());
+    assert_eq!(requests.try_iter().count(), 1);
+}
+
+#[test]
+fn rejects_zero_attempts() {
+    let result = OutlineVPN::builder()
+        .api_url("https://127.0.0.1:1234/secret")
+        .cert_sha256(CERT_SHA256)
+        .retry_policy(RetryPolicy::new(0))
+        .build();
+
+    assert!(matches!(
+        result,
+        Err(APIError::InvalidConfig {
+            field: "retry_policy",
+            ..
+        })
+    ));
+}
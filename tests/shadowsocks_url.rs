@@ -0,0 +1,135 @@
+use outline_api::{ShadowsocksUrl, ShadowsocksUrlError};
+
+#[test]
+fn parses_outline_access_url() {
+    let raw = "ss://Y2hhY2hhMjAtaWV0Zi1wb2x5MTMwNTpwYXNzd29yZA==@203.0.113.7:4410/?outline=1";
+
+    let url: ShadowsocksUrl = raw.parse().unwrap();
+
+    assert_eq!(url.method(), "chacha20-ietf-poly1305");
+    assert_eq!(url.password(), "password");
+    assert_eq!(url.host(), "203.0.113.7");
+    assert_eq!(url.port(), 4410);
+    assert!(url.is_outline());
+    assert_eq!(url.tag(), None);
+    assert_eq!(url.to_string(), raw);
+}
+
+#[test]
+fn parses_url_safe_base64_and_tag() {
+    let raw = "ss://YWVzLTI1Ni1nY206Pz8_Pz8-Pj4@example.com:8388#Caf%C3%A9%20%231";
+
+    let url: ShadowsocksUrl = raw.parse().unwrap();
+
+    assert_eq!(url.method(), "aes-256-gcm");
+    assert_eq!(url.password(), "?????>>>");
+    assert_eq!(url.tag(), Some("Café #1"));
+    assert_eq!(url.to_string(), raw);
+}
+
+#[test]
+fn parses_plain_userinfo_and_ipv6_host() {
+    let raw = "ss://2022-blake3-aes-256-gcm:YctPZ6U7xPPcU%2Bgp3u%2B0tx%2FtRizJN9K8y%2BuKlW2qjlI%3D@[2001:db8::1]:8888/?plugin=v2ray-plugin%3Bserver#Example";
+
+    let url: ShadowsocksUrl = raw.parse().unwrap();
+
+    assert_eq!(url.method(), "2022-blake3-aes-256-gcm");
+    assert_eq!(
+        url.password(),
+        "YctPZ6U7xPPcU+gp3u+0tx/tRizJN9K8y+uKlW2qjlI="
+    );
+    assert_eq!(url.host(), "2001:db8::1");
+    assert_eq!(url.port(), 8888);
+    assert_eq!(url.query_param("plugin"), Some("v2ray-plugin;server"));
+    assert_eq!(url.to_string(), raw);
+}
+
+#[test]
+fn rewrites_host() {
+    let url: ShadowsocksUrl =
+        "ss://Y2hhY2hhMjAtaWV0Zi1wb2x5MTMwNTpwYXNzd29yZA==@203.0.113.7:4410/?outline=1"
+            .parse()
+            .unwrap();
+
+    assert_eq!(
+        url.with_host("2001:db8::2").to_string(),
+        "ss://Y2hhY2hhMjAtaWV0Zi1wb2x5MTMwNTpwYXNzd29yZA==@[2001:db8::2]:4410/?outline=1"
+    );
+}
+
+#[test]
+fn accepts_bracketed_ipv6_host() {
+    let url = ShadowsocksUrl::new("chacha20-ietf-poly1305", "password", "[::1]", 8388)
+        .unwrap()
+        .with_host("[::2]");
+
+    assert_eq!(url.host(), "::2");
+    let raw = url.to_string();
+    assert!(raw.ends_with("@[::2]:8388"));
+    assert_eq!(raw.parse::<ShadowsocksUrl>().unwrap(), url);
+}
+
+#[test]
+fn builds_sip002_url() {
+    let url = ShadowsocksUrl::new("aes-128-gcm", "test", "192.168.100.1", 8888)
+        .unwrap()
+        .with_tag(Some("Example1".to_string()));
+
+    assert_eq!(
+        url.to_string(),
+        "ss://YWVzLTEyOC1nY206dGVzdA@192.168.100.1:8888#Example1"
+    );
+    assert_eq!(url.to_string().parse::<ShadowsocksUrl>().unwrap(), url);
+}
+
+#[test]
+fn rejects_port_zero() {
+    assert_eq!(
+        ShadowsocksUrl::new("aes-128-gcm", "test", "192.168.100.1", 0),
+        Err(ShadowsocksUrlError::InvalidPort)
+    );
+    let url = ShadowsocksUrl::new("aes-128-gcm", "test", "192.168.100.1", 8888).unwrap();
+    assert_eq!(url.with_port(0), Err(ShadowsocksUrlError::InvalidPort));
+}
+
+#[test]
+fn rejects_malformed_urls() {
+    let cases = [
+        ("http://example.com", ShadowsocksUrlError::InvalidScheme),
+        ("ss://example.com:80", ShadowsocksUrlError::MissingUserInfo),
+        (
+            "ss://!!!@example.com:80",
+            ShadowsocksUrlError::InvalidUserInfo,
+        ),
+        (
+            "ss://bm9jb2xvbg@example.com:80",
+            ShadowsocksUrlError::InvalidUserInfo,
+        ),
+        ("ss://YTpi@:80", ShadowsocksUrlError::InvalidHost),
+        ("ss://YTpi@example.com", ShadowsocksUrlError::InvalidPort),
+        ("ss://YTpi@example.com:0", ShadowsocksUrlError::InvalidPort),
+        (
+            "ss://YTpi@example.com:65536",
+            ShadowsocksUrlError::InvalidPort,
+        ),
+    ];
+
+    for (raw, error) in cases {
+        assert_eq!(raw.parse::<ShadowsocksUrl>(), Err(error), "{}", raw);
+    }
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn parses_access_keys_from_server() {
+    let server = outline_api::mock::MockServer::start();
+    let vpn = server.client();
+    let key = vpn.create_access_key().unwrap();
+
+    let url = key.shadowsocks_url().unwrap();
+
+    assert_eq!(url.method(), key.method);
+    assert_eq!(url.password(), key.password);
+    assert_eq!(url.port(), key.port);
+    assert_eq!(url.to_string(), key.access_url);
+}